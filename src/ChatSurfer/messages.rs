@@ -1,11 +1,14 @@
 use std::{
     collections::HashMap,
     fmt,
+    str::FromStr,
+    time::Duration,
 };
 
 //use strum::Display;
 use serde::{ Deserialize, Serialize };
 use strum_macros::{ EnumString, Display };
+use time::{ format_description::well_known::Iso8601, OffsetDateTime };
 use uuid::Uuid;
 
 const MAX_ERROR_ARGUMENTS: usize = 1;
@@ -14,10 +17,48 @@ const POINTS_IN_POLYGON: usize = 4;
 pub const MAX_REGIONS: usize = 1;
 pub const MAX_REGION_BOUNDS: usize = 4;
 pub const MAX_MESSAGE_GEOTAGS: usize = 1;
+const SECONDS_PER_MINUTE: u64 = 60;
+const SECONDS_PER_HOUR: u64 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY: u64 = 24 * SECONDS_PER_HOUR;
 
 // Classification strings
 pub const UNCLASSIFIED_STRING: &str = "UNCLASSIFIED";
 
+/// This enum lists the classification levels a marking can carry, ordered
+/// from lowest to highest.
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, EnumString, Display)]
+pub enum ClassificationLevel {
+    #[strum(to_string = "UNCLASSIFIED", serialize = "U")]
+    UNCLASSIFIED,
+
+    #[strum(to_string = "CONFIDENTIAL", serialize = "C")]
+    CONFIDENTIAL,
+
+    #[strum(to_string = "SECRET", serialize = "S")]
+    SECRET,
+
+    #[strum(to_string = "TOP SECRET", serialize = "TS")]
+    TOP_SECRET,
+}
+
+impl ClassificationLevel {
+    /*
+     * This method extracts the classification level from a full marking
+     * such as "SECRET//NOFORN", ignoring any caveats after the level.
+     */
+    pub fn from_marking(marking: &str) -> Option<ClassificationLevel> {
+        let level = marking
+            .split("//")
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_uppercase();
+
+        ClassificationLevel::from_str(&level).ok()
+    }
+}
+
 // =============================================================================
 // Error Messages
 
@@ -108,6 +149,16 @@ pub enum NetworkId {
     unclass,
 }
 
+impl NetworkId {
+    /*
+     * This method gives the name of the XMPP domain that ChatSurfer hosts on
+     * this network, e.g. chatsurferxmppunclass for unclass.
+     */
+    pub fn default_domain(&self) -> String {
+        format!("chatsurferxmpp{}", self)
+    }
+} // end NetworkId
+
 #[allow(non_camel_case_types)]
 #[derive(Debug, PartialEq, EnumString, Display)]
 pub enum JoinStatus {
@@ -132,10 +183,10 @@ pub struct LocationCoordinatesSchema {
     r#type:                 LocationType,
 
     // The first entry represents the coordinates for a single point.
-    point_coordinates:      [f32; COORDINATES_IN_POINT],
+    point_coordinates:      [f64; COORDINATES_IN_POINT],
     
     // The second entry represents a set of points for a polygon.
-    polygon_coordinates:    [[f32; COORDINATES_IN_POINT]; POINTS_IN_POLYGON],
+    polygon_coordinates:    [[f64; COORDINATES_IN_POINT]; POINTS_IN_POLYGON],
 
 }
 
@@ -153,7 +204,7 @@ impl LocationCoordinatesSchema {
     pub fn to_json(&self) -> String {
         let mut point_index: usize = 0;
         let mut polygon_index: usize = 0;
-        let mut value: f32;
+        let mut value: f64;
         let mut value_string: String;
         let mut point_string: String;
         let mut polygon_string: String;
@@ -239,6 +290,49 @@ impl LocationCoordinatesSchema {
         // for the polygon_coordinates field.
         format!("{}\"polygon_coordinates\":[{}]}}", json_string, polygon_string)
     } //end to_json
+
+    /*
+     * This method checks the point, or each point of the polygon, against
+     * the valid ranges for longitude and latitude.  Points are in GeoJSON
+     * order, longitude first.
+     */
+    pub fn validate(&self, location_type: &LocationType) -> Result<(), String> {
+        // Copy the coordinates out, since fields of a packed struct can't be
+        // borrowed.
+        let point_coordinates = self.point_coordinates;
+        let polygon_coordinates = self.polygon_coordinates;
+
+        match location_type {
+            LocationType::Point => validate_point(&point_coordinates)
+                .map_err(|e| format!("point: {}", e)),
+            // A polygon with fewer than POINTS_IN_POLYGON points doesn't
+            // deserialize, so only the points themselves need checking.
+            LocationType::Polygon => {
+                for (index, point) in polygon_coordinates.iter().enumerate() {
+                    validate_point(point).map_err(|e| format!("polygon point {}: {}", index, e))?;
+                }
+                Ok(())
+            }
+        }
+    } // end validate
+}
+
+/*
+ * This function checks that a [longitude, latitude] pair is within
+ * [-180, 180] and [-90, 90] respectively.
+ */
+fn validate_point(point: &[f64; COORDINATES_IN_POINT]) -> Result<(), String> {
+    let [longitude, latitude] = *point;
+
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!("longitude {} is outside [-180, 180].", longitude));
+    }
+
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(format!("latitude {} is outside [-90, 90].", latitude));
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, PartialEq, EnumString, Display)]
@@ -284,6 +378,13 @@ impl LocationSchema {
     pub fn to_json(&self) -> String {
         format!("{{\"coordinates\":{},\"type\":{}}}", self.coordinates, self.r#type)
     }
+
+    /*
+     * This method checks that the location's coordinates are on the globe.
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        self.coordinates.validate(&self.r#type)
+    }
 }
 
 //==============================================================================
@@ -296,7 +397,7 @@ impl LocationSchema {
 #[derive(Serialize, Deserialize)]
 pub struct RegionSchema {
     pub abbreviation:   String,
-    pub bounds:         [f32; MAX_REGION_BOUNDS],
+    pub bounds:         [f64; MAX_REGION_BOUNDS],
     pub description:    String,
     pub name:           String,
     pub regionType:     String
@@ -329,7 +430,7 @@ pub struct GeoTagSchema {
     pub anchorEnd:      i64,
     pub anchorStart:    i64,
     pub anchorText:     String,
-    pub confidence:     f32,
+    pub confidence:     f64,
     pub location:       LocationSchema,
     pub regions:        [RegionSchema; MAX_REGIONS],
     pub r#type:         String
@@ -356,7 +457,10 @@ impl GeoTagSchema {
 pub struct ChatMessageSchema {
     pub classification: String,
     pub domainId:       String,
-    pub geoTags:        [GeoTagSchema; MAX_MESSAGE_GEOTAGS],
+
+    // The server omits geo-tags unless the search asked for them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geoTags:        Option<[GeoTagSchema; MAX_MESSAGE_GEOTAGS]>,
     pub id:             Uuid,
     pub roomName:       String,
     pub sender:         String,
@@ -376,6 +480,26 @@ impl ChatMessageSchema {
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /*
+     * This method constructs an unclassified message with the given text in
+     * the test room, for use as a test fixture.
+     */
+    #[cfg(test)]
+    pub fn test(text: &str) -> ChatMessageSchema {
+        ChatMessageSchema {
+            classification: String::from(UNCLASSIFIED_STRING),
+            domainId:       String::from("chatsurferxmppunclass"),
+            geoTags:        None,
+            id:             Uuid::parse_str("6e4b6e86-030b-41ed-90ab-c05325526a01").unwrap(),
+            roomName:       String::from("edge-view-test-room"),
+            sender:         String::from("test.user"),
+            text:           String::from(text),
+            threadId:       Uuid::parse_str("6e4b6e86-030b-41ed-90ab-c05325526a02").unwrap(),
+            timestamp:      String::from("2024-05-01T12:00:00.000Z"),
+            userId:         Uuid::parse_str("6e4b6e86-030b-41ed-90ab-c05325526a03").unwrap()
+        }
+    }
 }
 
 // =============================================================================
@@ -398,6 +522,18 @@ impl GetChatMessagesResponse {
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /*
+     * This method constructs an unclassified response carrying the given
+     * messages, for use as a test fixture.
+     */
+    #[cfg(test)]
+    pub fn test(messages: Vec<ChatMessageSchema>) -> GetChatMessagesResponse {
+        GetChatMessagesResponse {
+            classification: String::from(UNCLASSIFIED_STRING),
+            messages
+        }
+    }
 }
 
 // #############################################################################
@@ -432,6 +568,25 @@ impl KeywordFilter {
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /*
+     * This method builds a KeywordFilter that matches any of the given
+     * keywords.  Blank keywords are dropped, and at least one keyword must
+     * remain.
+     */
+    pub fn try_from_vec(keywords: Vec<String>) -> Result<KeywordFilter, String> {
+        let keywords: Vec<String> = keywords
+            .into_iter()
+            .map(|keyword| String::from(keyword.trim()))
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+
+        if keywords.is_empty() {
+            return Err(String::from("A keyword filter needs at least one keyword."));
+        }
+
+        Ok(KeywordFilter { query: keywords.join(" ") })
+    }
 } // end KeywordFilter
 
 // =============================================================================
@@ -480,7 +635,7 @@ pub struct DomainFilterDetail  {
 // =============================================================================
 #[allow(non_camel_case_types)]
 #[derive(Serialize, Deserialize)]
-#[derive(Debug, PartialEq, EnumString, Display)]
+#[derive(Clone, Copy, Debug, PartialEq, EnumString, Display)]
 pub enum SortDirection {
     #[strum(serialize = "ASC")]
     ASC,
@@ -490,7 +645,7 @@ pub enum SortDirection {
 
 #[allow(non_camel_case_types)]
 #[derive(Serialize, Deserialize)]
-#[derive(Debug, PartialEq, EnumString, Display)]
+#[derive(Clone, Copy, Debug, PartialEq, EnumString, Display)]
 pub enum SortField {
     #[strum(serialize = "DOMAIN")]
     DOMAIN,
@@ -504,7 +659,7 @@ pub enum SortField {
     TIME,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SortFilter {
     pub orders: Vec<(SortDirection, SortField)>,
 }
@@ -540,23 +695,13 @@ pub struct ThreadIdFilter {
  * in the ChatSurfer API.
  */
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct TimeFilterRequest {
     endDateTime:        Option<String>, //This string needs to be in DateTime format.
     lookBackDuration:   Option<String>,
     startDateTime:      Option<String>, //This string needs to be in DateTime format.
 }
 
-impl Default for TimeFilterRequest {
-    fn default() -> Self {
-        TimeFilterRequest {
-            endDateTime:        Some(String::new()),
-            lookBackDuration:   Some(String::new()),
-            startDateTime:      Some(String::new()),
-        }
-    }
-}
-
 /*
  * Implement the trait fmt::Display for the struct TimeFilterRequest
  * so that these structs can be easily printed to consoles.
@@ -568,6 +713,56 @@ impl fmt::Display for TimeFilterRequest {
 }
 
 impl TimeFilterRequest {
+    /*
+     * This method constructs a TimeFilterRequest, checking that the start
+     * and end are ISO-8601 DateTimes (such as 2024-05-01T12:00:00Z) and
+     * that the start is not after the end.
+     */
+    pub fn new(
+        start:      Option<&str>,
+        end:        Option<&str>,
+        look_back:  Option<&str>,
+    ) -> Result<TimeFilterRequest, String> {
+        // A look-back is measured from now, so it can't be combined with a
+        // fixed start or end.
+        if look_back.is_some() && (start.is_some() || end.is_some()) {
+            return Err(String::from("A time filter can have a look-back or a start and end, but not both."));
+        }
+
+        if let Some(look_back) = look_back {
+            parse_look_back(look_back)?;
+        }
+
+        let start_date_time = start.map(|start| parse_date_time("start", start)).transpose()?;
+        let end_date_time = end.map(|end| parse_date_time("end", end)).transpose()?;
+
+        if let (Some(start_date_time), Some(end_date_time)) = (start_date_time, end_date_time) {
+            if start_date_time > end_date_time {
+                return Err(format!("The time filter's start {} is after its end {}.",
+                    start.unwrap_or_default(),
+                    end.unwrap_or_default()));
+            }
+        }
+
+        Ok(TimeFilterRequest {
+            endDateTime:        end.map(String::from),
+            lookBackDuration:   look_back.map(String::from),
+            startDateTime:      start.map(String::from),
+        })
+    }
+
+    /*
+     * This method constructs a TimeFilterRequest matching messages sent
+     * within the given duration of now.
+     */
+    pub fn from_look_back(look_back: Duration) -> TimeFilterRequest {
+        TimeFilterRequest {
+            endDateTime:        None,
+            lookBackDuration:   Some(format_look_back(look_back)),
+            startDateTime:      None,
+        }
+    }
+
     /*
      * This method constructs a JSON string from the TimeFilterRequest's
      * fields.
@@ -577,6 +772,129 @@ impl TimeFilterRequest {
     }
 }
 
+/*
+ * This function formats a duration as an ISO-8601 duration, such as
+ * P1DT2H30M, which is the form ChatSurfer expects for a look-back.
+ */
+pub fn format_look_back(look_back: Duration) -> String {
+    let total_seconds = look_back.as_secs();
+    let days = total_seconds / SECONDS_PER_DAY;
+    let hours = total_seconds % SECONDS_PER_DAY / SECONDS_PER_HOUR;
+    let minutes = total_seconds % SECONDS_PER_HOUR / SECONDS_PER_MINUTE;
+    let seconds = total_seconds % SECONDS_PER_MINUTE;
+    let nanos = look_back.subsec_nanos();
+
+    let mut formatted = String::from("P");
+
+    if days > 0 {
+        formatted += &format!("{}D", days);
+    }
+
+    if hours > 0 || minutes > 0 || seconds > 0 || nanos > 0 || days == 0 {
+        formatted += "T";
+    }
+
+    if hours > 0 {
+        formatted += &format!("{}H", hours);
+    }
+
+    if minutes > 0 {
+        formatted += &format!("{}M", minutes);
+    }
+
+    if nanos > 0 {
+        let fraction = format!("{:09}", nanos);
+        formatted += &format!("{}.{}S", seconds, fraction.trim_end_matches('0'));
+    } else if seconds > 0 || formatted == "PT" {
+        formatted += &format!("{}S", seconds);
+    }
+
+    formatted
+} // end format_look_back
+
+/*
+ * This function parses an ISO-8601 duration given as a look-back, such as
+ * PT1H or P2DT12H.  Years and months are refused, since they have no fixed
+ * length, as is a look-back of zero, which would match nothing.
+ */
+pub fn parse_look_back(look_back: &str) -> Result<Duration, String> {
+    let invalid = |reason: &str| format!("The look-back \"{}\" is not a valid ISO-8601 duration: {}", look_back, reason);
+
+    let designators = look_back
+        .strip_prefix('P')
+        .ok_or_else(|| invalid("it must start with P"))?;
+
+    let mut total = Duration::ZERO;
+    let mut in_time = false;
+    let mut components: usize = 0;
+    let mut number = String::new();
+
+    for c in designators.chars() {
+        let unit_seconds = match (c, in_time) {
+            ('0'..='9', _) | ('.', true) => {
+                number.push(c);
+                continue;
+            }
+            ('T', false) if number.is_empty() => {
+                in_time = true;
+                continue;
+            }
+            ('W', false) => SECONDS_PER_DAY * 7,
+            ('D', false) => SECONDS_PER_DAY,
+            ('H', true)  => SECONDS_PER_HOUR,
+            ('M', true)  => SECONDS_PER_MINUTE,
+            ('S', true)  => 1,
+            ('Y', false) | ('M', false) => {
+                return Err(invalid("years and months have no fixed length, use weeks or days"));
+            }
+            _ => return Err(invalid(&format!("unexpected '{}'", c))),
+        };
+
+        let value: f64 = number
+            .parse()
+            .map_err(|_| invalid(&format!("'{}' has no number before it", c)))?;
+
+        if c != 'S' && number.contains('.') {
+            return Err(invalid("only seconds can have a fraction"));
+        }
+
+        let component = Duration::try_from_secs_f64(value * unit_seconds as f64)
+            .map_err(|_| invalid("it is too long"))?;
+
+        total = total
+            .checked_add(component)
+            .ok_or_else(|| invalid("it is too long"))?;
+        components += 1;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(invalid("it ends with a number that has no unit"));
+    }
+
+    if designators.ends_with('T') {
+        return Err(invalid("T must be followed by hours, minutes, or seconds"));
+    }
+
+    if components == 0 {
+        return Err(invalid("it has no durations in it"));
+    }
+
+    if total.is_zero() {
+        return Err(invalid("a look-back of zero would match nothing"));
+    }
+
+    Ok(total)
+} // end parse_look_back
+
+/*
+ * This function parses one end of a time filter as an ISO-8601 DateTime.
+ */
+fn parse_date_time(which: &str, date_time: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(date_time, &Iso8601::DEFAULT)
+        .map_err(|e| format!("The time filter's {} \"{}\" is not an ISO-8601 DateTime: {}", which, date_time, e))
+} // end parse_date_time
+
 // =============================================================================
 // struct UserIdFilter
 // =============================================================================
@@ -655,8 +973,106 @@ impl SearchChatMessagesRequest {
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    pub fn builder() -> SearchChatMessagesRequestBuilder {
+        SearchChatMessagesRequestBuilder::new()
+    }
+}
+
+// =============================================================================
+// struct SearchChatMessagesRequestBuilder
+// =============================================================================
+
+/// The SearchChatMessagesRequestBuilder structure sets the commonly used
+/// filters of a SearchChatMessagesRequest one at a time.  Filters that are
+/// not set stay None.
+pub struct SearchChatMessagesRequestBuilder {
+    request:    SearchChatMessagesRequest,
+    error:      Option<String>,
+}
+
+impl Default for SearchChatMessagesRequestBuilder {
+    fn default() -> Self {
+        SearchChatMessagesRequestBuilder::new()
+    }
 }
 
+impl SearchChatMessagesRequestBuilder {
+    pub fn new() -> SearchChatMessagesRequestBuilder {
+        SearchChatMessagesRequestBuilder {
+            request:    SearchChatMessagesRequest::default(),
+            error:      None,
+        }
+    }
+
+    /*
+     * This method restricts the search to messages containing any of the
+     * given keywords.
+     */
+    pub fn keywords(mut self, keywords: Vec<String>) -> Self {
+        match KeywordFilter::try_from_vec(keywords) {
+            Ok(filter) => self.request.keywordFilter = Some(filter),
+            Err(e) => self.error = self.error.or(Some(e)),
+        }
+        self
+    }
+
+    /*
+     * This method limits the number of messages returned per page.
+     */
+    pub fn limit(mut self, limit: i32) -> Self {
+        self.request.limit = Some(limit);
+        self
+    }
+
+    /*
+     * This method restricts the search to messages sent between the given
+     * DateTimes.
+     */
+    pub fn time_range(mut self, start: String, end: String) -> Self {
+        match TimeFilterRequest::new(Some(&start), Some(&end), None) {
+            Ok(filter) => self.request.timeFilter = Some(filter),
+            Err(e) => self.error = self.error.or(Some(e)),
+        }
+        self
+    }
+
+    /*
+     * This method adds an ordering to the results.  Orderings apply in the
+     * order they were added.
+     */
+    pub fn sort(mut self, field: SortField, direction: SortDirection) -> Self {
+        self.request.sort
+            .get_or_insert_with(|| SortFilter { orders: Vec::new() })
+            .orders
+            .push((direction, field));
+        self
+    }
+
+    /*
+     * This method restricts the search to the given rooms of a domain.  It
+     * may be called once for each domain.
+     */
+    pub fn room_filter(mut self, domain: String, rooms: Vec<String>) -> Self {
+        self.request.roomFilter
+            .get_or_insert_with(|| DomainFilterDetail { domains: HashMap::new() })
+            .domains
+            .insert(domain, DomainFilterProperties { properties: rooms });
+        self
+    }
+
+    /*
+     * This method returns the finished request, or the first problem found
+     * with the filters given.
+     */
+    pub fn build(self) -> Result<SearchChatMessagesRequest, String> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.request),
+        }
+    }
+} // end SearchChatMessagesRequestBuilder
+
 #[derive(Serialize, Deserialize)]
 pub enum SearchChatMessagesResponseTypes {
     Success200 { status_code: u16, body: SearchChatMessagesResponse },
@@ -688,7 +1104,10 @@ pub struct SearchChatMessagesResponse {
     pub classification:     String,
     pub messages:           Option<Vec<ChatMessageSchema>>,
     pub nextCursorMark:     Option<String>,
-    pub searchTimeFiler:    TimeFilterResponse,
+    // This field was once misspelled "searchTimeFiler" in this struct, so
+    // that spelling is still accepted when parsing.
+    #[serde(alias = "searchTimeFiler")]
+    pub searchTimeFilter:   TimeFilterResponse,
     pub total:              i32,
 }
 
@@ -710,6 +1129,23 @@ impl SearchChatMessagesResponse {
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /*
+     * This method constructs an unclassified page of search results
+     * carrying the given messages, for use as a test fixture.
+     */
+    #[cfg(test)]
+    pub fn test(messages: Vec<ChatMessageSchema>, next_cursor_mark: Option<&str>) -> SearchChatMessagesResponse {
+        SearchChatMessagesResponse {
+            classification:     String::from(UNCLASSIFIED_STRING),
+            total:              messages.len() as i32,
+            messages:           Some(messages),
+            nextCursorMark:     next_cursor_mark.map(String::from),
+            searchTimeFilter:   TimeFilterResponse {
+                endDateTime:    String::from("2024-05-01T12:30:00.000Z"),
+            },
+        }
+    }
 }
 
 // =============================================================================
@@ -762,6 +1198,20 @@ impl SendChatMessageRequest {
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /*
+     * This method constructs a request to send the given text to the test
+     * room, for use as a test fixture.
+     */
+    #[cfg(test)]
+    pub fn test(text: &str) -> SendChatMessageRequest {
+        SendChatMessageRequest {
+            domainId:   String::from("chatsurferxmppunclass"),
+            message:    String::from(text),
+            roomName:   String::from("edge-view-test-room"),
+            ..Default::default()
+        }
+    }
 } //end SendChatMessageRequest
 
 #[derive(Serialize, Deserialize)]
@@ -783,4 +1233,252 @@ impl fmt::Display for CreateMessageResponse {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A geo-tag as the server sends it, with coordinates given to more
+    // places than an f32 can hold.
+    const GEO_TAG_JSON: &str = r#"{
+        "anchorEnd": 7,
+        "anchorStart": 0,
+        "anchorText": "Norfolk",
+        "confidence": 0.987654321012,
+        "location": {
+            "coordinates": {
+                "point_coordinates": [-76.285873412345, 36.850768123456],
+                "polygon_coordinates": [
+                    [-76.2901234567, 36.8401234567],
+                    [-76.2701234567, 36.8401234567],
+                    [-76.2701234567, 36.8601234567],
+                    [-76.2901234567, 36.8601234567]
+                ]
+            },
+            "type": "Point"
+        },
+        "regions": [{
+            "abbreviation": "VA",
+            "bounds": [-83.675395123, 36.540738456, -75.242266789, 39.466012345],
+            "description": "Commonwealth of Virginia",
+            "name": "Virginia",
+            "regionType": "State"
+        }],
+        "type": "LOCATION"
+    }"#;
+
+    #[test]
+    fn geo_tag_round_trip_keeps_full_precision() {
+        let sent: serde_json::Value = serde_json::from_str(GEO_TAG_JSON).unwrap();
+        let geo_tag: GeoTagSchema = serde_json::from_str(GEO_TAG_JSON).unwrap();
+        let stored: serde_json::Value = serde_json::from_str(&geo_tag.to_json()).unwrap();
+
+        assert_eq!(stored, sent);
+    }
+
+    // Parses a location with the given point and a square polygon whose
+    // first corner is the given polygon point.
+    fn location(point: &str, polygon_point: &str, location_type: &str) -> LocationSchema {
+        let json = format!(r#"{{
+            "coordinates": {{
+                "point_coordinates": {},
+                "polygon_coordinates": [{}, [-76.27, 36.84], [-76.27, 36.86], [-76.29, 36.86]]
+            }},
+            "type": "{}"
+        }}"#, point, polygon_point, location_type);
+
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn locations_on_the_globe_are_valid() {
+        assert_eq!(location("[-76.28, 36.85]", "[-76.29, 36.84]", "Point").validate(), Ok(()));
+        assert_eq!(location("[-76.28, 36.85]", "[-76.29, 36.84]", "Polygon").validate(), Ok(()));
+        assert_eq!(location("[180.0, -90.0]", "[-180.0, 90.0]", "Point").validate(), Ok(()));
+    }
+
+    #[test]
+    fn out_of_range_point_is_invalid() {
+        assert_eq!(location("[-181.0, 36.85]", "[-76.29, 36.84]", "Point").validate(),
+            Err(String::from("point: longitude -181 is outside [-180, 180].")));
+        assert_eq!(location("[-76.28, 90.5]", "[-76.29, 36.84]", "Point").validate(),
+            Err(String::from("point: latitude 90.5 is outside [-90, 90].")));
+    }
+
+    #[test]
+    fn out_of_range_polygon_point_is_invalid() {
+        assert_eq!(location("[-76.28, 36.85]", "[-76.29, -91.0]", "Polygon").validate(),
+            Err(String::from("polygon point 0: latitude -91 is outside [-90, 90].")));
+
+        // Only the coordinates the type calls for are checked.
+        assert_eq!(location("[-76.28, 36.85]", "[-76.29, -91.0]", "Point").validate(), Ok(()));
+    }
+
+    #[test]
+    fn polygon_with_too_few_points_does_not_parse() {
+        let json = r#"{
+            "point_coordinates": [-76.28, 36.85],
+            "polygon_coordinates": [[-76.29, 36.84], [-76.27, 36.84], [-76.27, 36.86]]
+        }"#;
+
+        assert!(serde_json::from_str::<LocationCoordinatesSchema>(json).is_err());
+    }
+
+    // Serializes a value with serde and parses it back, so that a field
+    // that is renamed one way but not the other fails to parse.
+    fn round_trip<T: Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+        serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn chat_message_round_trips() {
+        let message = ChatMessageSchema::test("Convoy departed from Norfolk.");
+        let parsed = round_trip(&message);
+
+        assert_eq!(parsed.classification, message.classification);
+        assert_eq!(parsed.domainId, message.domainId);
+        assert!(parsed.geoTags.is_none());
+        assert_eq!(parsed.id, message.id);
+        assert_eq!(parsed.roomName, message.roomName);
+        assert_eq!(parsed.sender, message.sender);
+        assert_eq!(parsed.text, message.text);
+        assert_eq!(parsed.threadId, message.threadId);
+        assert_eq!(parsed.timestamp, message.timestamp);
+        assert_eq!(parsed.userId, message.userId);
+    }
+
+    #[test]
+    fn get_chat_messages_response_round_trips() {
+        let response = GetChatMessagesResponse::test(vec!(
+            ChatMessageSchema::test("Convoy departed from Norfolk."),
+            ChatMessageSchema::test("Convoy arrived in Richmond."),
+        ));
+        let parsed = round_trip(&response);
+
+        assert_eq!(parsed.classification, response.classification);
+        assert_eq!(parsed.messages.len(), 2);
+        assert_eq!(parsed.messages[1].text, "Convoy arrived in Richmond.");
+        assert_eq!(parsed.to_json(), response.to_json());
+    }
+
+    #[test]
+    fn search_chat_messages_response_round_trips() {
+        let response = SearchChatMessagesResponse::test(
+            vec!(ChatMessageSchema::test("Convoy departed from Norfolk.")),
+            Some("AoE1"));
+        let parsed = round_trip(&response);
+
+        assert_eq!(parsed.classification, response.classification);
+        assert_eq!(parsed.messages.as_ref().map(Vec::len), Some(1));
+        assert_eq!(parsed.nextCursorMark.as_deref(), Some("AoE1"));
+        assert_eq!(parsed.searchTimeFilter.endDateTime, response.searchTimeFilter.endDateTime);
+        assert_eq!(parsed.total, 1);
+        assert_eq!(parsed.to_json(), response.to_json());
+    }
+
+    #[test]
+    fn send_chat_message_request_round_trips() {
+        let request = SendChatMessageRequest::test("Convoy departed from Norfolk.");
+        let parsed = round_trip(&request);
+
+        assert_eq!(parsed.classification, request.classification);
+        assert_eq!(parsed.domainId, request.domainId);
+        assert_eq!(parsed.message, request.message);
+        assert_eq!(parsed.nickname, request.nickname);
+        assert_eq!(parsed.roomName, request.roomName);
+    }
+
+    #[test]
+    fn search_response_binds_search_time_filter() {
+        let json = r#"{
+            "classification": "UNCLASSIFIED",
+            "messages": [{
+                "classification": "UNCLASSIFIED",
+                "domainId": "chatsurferxmppunclass",
+                "id": "6e4b6e86-030b-41ed-90ab-c05325526a01",
+                "roomName": "edge-view-test-room",
+                "sender": "test.user",
+                "text": "Convoy departed from Norfolk.",
+                "threadId": "6e4b6e86-030b-41ed-90ab-c05325526a02",
+                "timestamp": "2024-05-01T12:00:00.000Z",
+                "userId": "6e4b6e86-030b-41ed-90ab-c05325526a03"
+            }],
+            "nextCursorMark": "AoE/GjZlNGI2ZTg2",
+            "searchTimeFilter": { "endDateTime": "2024-05-01T12:30:00.000Z" },
+            "total": 1
+        }"#;
+
+        let response: SearchChatMessagesResponse = serde_json::from_str(json).unwrap();
+
+        assert_eq!(response.searchTimeFilter.endDateTime, "2024-05-01T12:30:00.000Z");
+        assert_eq!(response.messages.unwrap().len(), 1);
+        assert_eq!(response.nextCursorMark.as_deref(), Some("AoE/GjZlNGI2ZTg2"));
+        assert_eq!(response.total, 1);
+    }
+
+    #[test]
+    fn search_response_accepts_the_old_spelling() {
+        let json = r#"{
+            "classification": "UNCLASSIFIED",
+            "messages": [],
+            "searchTimeFiler": { "endDateTime": "2024-05-01T12:30:00.000Z" },
+            "total": 0
+        }"#;
+
+        let response: SearchChatMessagesResponse = serde_json::from_str(json).unwrap();
+
+        assert_eq!(response.searchTimeFilter.endDateTime, "2024-05-01T12:30:00.000Z");
+    }
+
+    #[test]
+    fn search_response_round_trips_under_the_current_spelling() {
+        let json = r#"{
+            "classification": "UNCLASSIFIED",
+            "messages": [],
+            "searchTimeFiler": { "endDateTime": "2024-05-01T12:30:00.000Z" },
+            "total": 0
+        }"#;
+
+        let response: SearchChatMessagesResponse = serde_json::from_str(json).unwrap();
+        let serialized = response.to_json();
+
+        assert!(serialized.contains(r#""searchTimeFilter":{"endDateTime":"2024-05-01T12:30:00.000Z"}"#));
+        assert!(!serialized.contains("searchTimeFiler"));
+
+        let reparsed: SearchChatMessagesResponse = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.to_json(), serialized);
+    }
+
+    #[test]
+    fn look_back_round_trips_through_its_text() {
+        for look_back in ["PT1H", "P1DT2H30M", "P2D", "PT0.5S", "PT1M30.25S", "P3DT4S"] {
+            assert_eq!(format_look_back(parse_look_back(look_back).unwrap()), look_back);
+        }
+    }
+
+    #[test]
+    fn look_back_round_trips_through_its_duration() {
+        for seconds in [1, 59, 3600, 86_399, 86_400, 90_061, 604_800] {
+            let look_back = Duration::from_secs(seconds);
+
+            assert_eq!(parse_look_back(&format_look_back(look_back)), Ok(look_back));
+        }
+
+        let look_back = Duration::from_millis(90_061_250);
+        assert_eq!(parse_look_back(&format_look_back(look_back)), Ok(look_back));
+    }
+
+    #[test]
+    fn look_back_in_weeks_is_formatted_in_days() {
+        assert_eq!(parse_look_back("P2W"), Ok(Duration::from_secs(14 * 86_400)));
+        assert_eq!(format_look_back(parse_look_back("P2W").unwrap()), "P14D");
+    }
+
+    #[test]
+    fn malformed_look_back_is_rejected() {
+        for look_back in ["1H", "P", "PT", "P1M", "P1Y", "PT1.5H", "PT1", "P0D", "PT1H2D", "P99999999999999999999D"] {
+            assert!(parse_look_back(look_back).is_err(), "{} was accepted", look_back);
+        }
+    }
+}
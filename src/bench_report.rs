@@ -0,0 +1,199 @@
+use crate::test_case::{ percentile, TestResult };
+use serde::{ Deserialize, Serialize };
+use std::collections::BTreeMap;
+use tracing::{ event, Level };
+
+//==============================================================================
+// struct BenchReport
+//==============================================================================
+
+/// The BenchReport structure holds the latency of each endpoint over a run,
+/// as --bench_report saves it and --baseline reads it back.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct BenchReport {
+    pub endpoints:  BTreeMap<String, EndpointLatency>,
+}
+
+/// The EndpointLatency structure summarizes the latency of the tests that
+/// passed against one endpoint.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct EndpointLatency {
+    pub samples:    usize,
+    pub p50_us:     usize,
+    pub p99_us:     usize,
+}
+
+impl BenchReport {
+    pub fn new<'a>(results: impl IntoIterator<Item = &'a TestResult>) -> BenchReport {
+        let mut latencies: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+        // A failed test may have been cut short by an error or a timeout, so
+        // only the tests that passed say how fast the endpoint is.
+        for result in results.into_iter().filter(|result| result.passed) {
+            latencies
+                .entry(result.server_path.clone())
+                .or_default()
+                .push(result.latency.as_micros() as usize);
+        }
+
+        let endpoints = latencies
+            .into_iter()
+            .map(|(server_path, mut latencies)| {
+                latencies.sort_unstable();
+
+                (server_path, EndpointLatency {
+                    samples:    latencies.len(),
+                    p50_us:     percentile(&latencies, 50),
+                    p99_us:     percentile(&latencies, 99),
+                })
+            })
+            .collect();
+
+        BenchReport { endpoints }
+    }
+} // end BenchReport
+
+/*
+ * This function returns how many percent the current latency is above the
+ * baseline, or below it when negative.
+ */
+fn percent_change(baseline: usize, current: usize) -> f64 {
+    if baseline == current {
+        return 0.0;
+    }
+
+    (current as f64 - baseline as f64) / baseline as f64 * 100.0
+}
+
+/*
+ * This function compares the latencies of a run with a saved baseline,
+ * logging the p50 and p99 change of each endpoint.  It returns a
+ * description of each p50 or p99 that grew by more than the threshold
+ * percentage.
+ */
+pub fn compare_with_baseline(baseline: &BenchReport, current: &BenchReport, threshold: f64) -> Vec<String> {
+    let mut regressions: Vec<String> = Vec::new();
+
+    for (server_path, current) in &current.endpoints {
+        let Some(baseline) = baseline.endpoints.get(server_path) else {
+            event!(Level::INFO, "{}: not in the baseline, so there is nothing to compare.", server_path);
+            continue;
+        };
+
+        let p50_change = percent_change(baseline.p50_us, current.p50_us);
+        let p99_change = percent_change(baseline.p99_us, current.p99_us);
+
+        event!(Level::INFO,
+            "{}: p50 {} -> {} us ({:+.1}%), p99 {} -> {} us ({:+.1}%)",
+            server_path,
+            baseline.p50_us,
+            current.p50_us,
+            p50_change,
+            baseline.p99_us,
+            current.p99_us,
+            p99_change
+        );
+
+        for (name, change) in [("p50", p50_change), ("p99", p99_change)] {
+            if change > threshold {
+                regressions.push(format!("The {} latency of {} rose {:.1}%, more than the {}% allowed.",
+                    name,
+                    server_path,
+                    change,
+                    threshold));
+            }
+        }
+    }
+
+    regressions
+} // end compare_with_baseline
+
+/*
+ * This function writes the benchmark report for a run to the given path.
+ */
+pub fn write_bench_report(path: &str, report: &BenchReport) -> Result<(), String> {
+    let report = serde_json::to_string_pretty(report)
+        .map_err(|e| format!("Could not serialize the benchmark report: {}", e))?;
+
+    std::fs::write(path, report)
+        .map_err(|e| format!("Could not write the benchmark report to {}: {}", path, e))
+} // end write_bench_report
+
+/*
+ * This function reads a benchmark report saved by an earlier run.
+ */
+pub fn read_bench_report(path: &str) -> Result<BenchReport, String> {
+    let report = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read the baseline {}: {}", path, e))?;
+
+    serde_json::from_str(&report)
+        .map_err(|e| format!("Could not parse the baseline {}: {}", path, e))
+} // end read_bench_report
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::Algorithm;
+    use std::time::Duration;
+
+    fn result(server_path: &str, passed: bool, latency_ms: u64) -> TestResult {
+        TestResult {
+            name:           String::from("Test"),
+            server_path:    String::from(server_path),
+            jwt_header_alg: Algorithm::HS256,
+            passed,
+            failure:        None,
+            response_size:  None,
+            response:       None,
+            payload:        None,
+            latency:        Duration::from_millis(latency_ms),
+        }
+    }
+
+    fn endpoint(p50_us: usize, p99_us: usize) -> EndpointLatency {
+        EndpointLatency { samples: 10, p50_us, p99_us }
+    }
+
+    #[test]
+    fn only_passed_tests_count_towards_the_latency() {
+        let mut results: Vec<TestResult> = (1..=10).map(|ms| result("/search", true, ms)).collect();
+        results.push(result("/search", false, 5000));
+        results.push(result("/users", true, 3));
+
+        let report = BenchReport::new(&results);
+
+        assert_eq!(report.endpoints["/search"], EndpointLatency { samples: 10, p50_us: 5000, p99_us: 10000 });
+        assert_eq!(report.endpoints["/users"], EndpointLatency { samples: 1, p50_us: 3000, p99_us: 3000 });
+    }
+
+    #[test]
+    fn latency_above_the_threshold_is_a_regression() {
+        let baseline = BenchReport {
+            endpoints: BTreeMap::from([
+                (String::from("/search"), endpoint(1000, 2000)),
+                (String::from("/users"), endpoint(1000, 2000)),
+            ]),
+        };
+        let current = BenchReport {
+            endpoints: BTreeMap::from([
+                (String::from("/search"), endpoint(1050, 3000)),
+                (String::from("/users"), endpoint(500, 1000)),
+                (String::from("/send"), endpoint(9000, 9000)),
+            ]),
+        };
+
+        assert_eq!(compare_with_baseline(&baseline, &current, 10.0),
+            vec![String::from("The p99 latency of /search rose 50.0%, more than the 10% allowed.")]);
+        assert!(compare_with_baseline(&baseline, &current, 50.0).is_empty());
+    }
+
+    #[test]
+    fn report_survives_being_saved_and_read_back() {
+        let report = BenchReport {
+            endpoints: BTreeMap::from([(String::from("/search"), endpoint(1000, 2000))]),
+        };
+        let saved: BenchReport = serde_json::from_str(&serde_json::to_string_pretty(&report).unwrap()).unwrap();
+
+        assert_eq!(saved, report);
+    }
+}
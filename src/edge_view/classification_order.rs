@@ -0,0 +1,89 @@
+use crate::chatsurfer::messages::ClassificationLevel;
+use crate::edge_view::client::error;
+use std::collections::HashMap;
+
+//==============================================================================
+// struct ClassificationOrder
+//==============================================================================
+
+/// The ClassificationOrder structure follows the classification markings of
+/// each thread across a listen session and flags any message whose marking
+/// is lower than one seen earlier in the same thread.
+pub struct ClassificationOrder {
+    // The highest level seen so far in each thread, with its full marking.
+    highest:    HashMap<String, (ClassificationLevel, String)>,
+    violations: u32,
+}
+
+impl ClassificationOrder {
+    pub fn new() -> ClassificationOrder {
+        ClassificationOrder {
+            highest:    HashMap::new(),
+            violations: 0,
+        }
+    }
+
+    pub fn violations(&self) -> u32 {
+        self.violations
+    }
+
+    /*
+     * This method checks every chat message carried by a payload, either in
+     * its "messages" array or as the payload itself, against the markings
+     * seen earlier in the same thread.
+     */
+    pub fn check_payload(&mut self, payload: &str) {
+        let value: serde_json::Value = match serde_json::from_str(payload) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+
+        match value["messages"].as_array() {
+            Some(messages) => {
+                for message in messages {
+                    self.check_message(message);
+                }
+            }
+            None => self.check_message(&value),
+        }
+    }
+
+    fn check_message(&mut self, message: &serde_json::Value) {
+        let (thread_id, marking) = match (
+            message["threadId"].as_str(),
+            message["classification"].as_str()
+        ) {
+            (Some(thread_id), Some(marking)) => (thread_id, marking),
+            _ => return,
+        };
+
+        let level = match ClassificationLevel::from_marking(marking) {
+            Some(level) => level,
+            None => {
+                error(format!("Thread {}: message {} has an unrecognized classification \"{}\".",
+                    thread_id,
+                    message["id"],
+                    marking));
+                self.violations += 1;
+                return;
+            }
+        };
+
+        match self.highest.get(thread_id) {
+            Some((highest, highest_marking)) if level < *highest => {
+                error(format!("Thread {}: message {} is marked {} after the thread reached {}.",
+                    thread_id,
+                    message["id"],
+                    marking,
+                    highest_marking));
+                self.violations += 1;
+            }
+            Some((highest, _)) if level == *highest => {}
+            _ => {
+                self.highest.insert(
+                    String::from(thread_id),
+                    (level, String::from(marking)));
+            }
+        }
+    } // end check_message
+} // end ClassificationOrder
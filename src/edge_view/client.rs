@@ -1,4 +1,21 @@
+use crate::chatsurfer::messages::{
+    ChatMessageSchema,
+    ClassificationLevel,
+    SearchChatMessagesRequest,
+    SearchChatMessagesResponse,
+    SortDirection,
+    SortField,
+    TimeFilterRequest,
+};
+use crate::cli::Args;
 use crate::edge_view;
+use crate::edge_view::classification_order::ClassificationOrder;
+use crate::edge_view::connection_stats::{ ConnectionStats, CountingStream };
+use crate::edge_view::fault::{ self, Fault };
+use crate::edge_view::http2::{ self, ClientStream, Http2Connect };
+use crate::edge_view::proxy::Proxy;
+use crate::edge_view::shutdown;
+use clap::ValueEnum;
 use jsonwebtoken::{
     Algorithm,
     encode,
@@ -6,10 +23,12 @@ use jsonwebtoken::{
     Header,
 };
 use futures_util::{ SinkExt, StreamExt };
+use rand::{ Rng, SeedableRng, rngs::StdRng };
 use crate::messages;
 use messages::{
     Account,
     EdgeViewClaims,
+    EdgeViewResponseTypes,
     GetMessagesRequest,
     GetUsersRequest,
     RealmAccess,
@@ -18,13 +37,23 @@ use messages::{
     SearchMessagesRequest,
     SendNewMessageRequest,
 };
-use std::{thread, time};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    io::IsTerminal,
+    time,
+};
 use thread_id;
-use tokio::net::TcpStream;
+use tokio::{
+    io::{ AsyncBufReadExt, BufReader },
+    net::TcpStream,
+};
 use tokio_tungstenite::{
     client_async,
+    MaybeTlsStream,
     tungstenite::{
-        client::IntoClientRequest, http::HeaderValue, protocol::{CloseFrame, Message},
+        client::IntoClientRequest, handshake::client::Request, http::{ HeaderMap, HeaderName, HeaderValue, StatusCode }, protocol::{CloseFrame, Message, Role},
+        Error as TungsteniteError,
         protocol::frame::coding::CloseCode,
     },
     WebSocketStream,
@@ -33,8 +62,60 @@ use tracing::{event, Level};
 use uuid::Uuid;
 
 pub const SERVER_PORT: u16 = 7878;
-const TEST_DOMAIN: &str = "chatsurferxmppunclass";
-const TEST_ROOM: &str = "edge-view-test-room";
+
+// The shared secret used to sign JWTs with the HMAC algorithms.
+const JWT_SECRET: &str = "MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAzq/jsj5MTmOA9sW4YBJpv16yLPvznKLj3UqNXQ17WhukP5wu6GQyHMUSqNV8CAqGEA8TJpoQcpTCs8iaKxpfF1yORKdeuvCa/aJZpOw6TwsJZa1OWLONyJnOuPeZZNDUn+D7as+tS9ws7UP3AtROO8hkMS7+B3C90eXTWhZnkzEDSfDmfUxPMvYH/5yGUI4AtzbAGPMwiDOXOguXUSkV5TP7RXTZqrgHp3yvzBsbaWtjW9r4tfzXRHuGFXhlEgBdsBIzupaXrpfqIjHQXDhJ1NnI6KOQUTDi5t3VOhfZ8z6WXMPdqi/pvyzTenAshvoTR2rEti6KyLqwTdW6y1KFVQIDAQAB";
+
+// The WebSocket connection type handed out by ws_connect.  The underlying
+// stream counts the bytes that pass through it.
+pub type ClientSocket = WebSocketStream<CountingStream<ClientStream>>;
+pub const TEST_DOMAIN: &str = "chatsurferxmppunclass";
+pub const TEST_ROOM: &str = "edge-view-test-room";
+
+// The text sent by the Send New Message tests when --message_text isn't
+// given.
+pub const DEFAULT_MESSAGE_TEXT: &str = "I'm a new message";
+
+// The keyword searched for when --search_keywords isn't given.
+const DEFAULT_SEARCH_KEYWORD: &str = "test_keyword";
+
+// How many suppressed duplicate frames pass between progress messages
+// when listening with deduplication enabled.
+const DUPLICATE_SUMMARY_INTERVAL: u32 = 10;
+
+// How often interactive mode pings the server to keep the connection alive.
+const INTERACTIVE_HEARTBEAT_SECS: u64 = 30;
+
+// How long interactive mode waits for outstanding responses after EOF.
+const INTERACTIVE_DRAIN_SECS: u64 = 5;
+
+// How long to wait before the first retry of a failed DNS resolution.  The
+// wait doubles with each further retry.
+const DNS_RETRY_INITIAL_BACKOFF_MS: u64 = 500;
+
+// The field added to each pipelined request, which the server is expected
+// to echo in the matching response.
+const CORRELATION_ID_FIELD: &str = "correlationId";
+
+// How long the listen test waits to close a stalled connection before
+// reconnecting anyway.
+const STALLED_CLOSE_TIMEOUT_MS: u64 = 1000;
+
+// How many messages --search_all asks for in each page.
+const SEARCH_ALL_PAGE_SIZE: i32 = 50;
+
+// How long the pipeline test waits for each outstanding response.
+const PIPELINE_RESPONSE_SECS: u64 = 10;
+
+// Responses nested deeper than this are treated as malformed.  serde_json
+// refuses to parse anything nested 128 levels or more, so this is the
+// deepest document it will still accept.
+pub const MAX_JSON_DEPTH: usize = 127;
+
+// Word lists used to generate the messages that seed the test room.
+const SEED_SUBJECTS: [&str; 6] = ["Team", "Shipment", "Flight", "Vehicle", "Survey crew", "Weather balloon"];
+const SEED_ACTIONS: [&str; 5] = ["reported near", "departed from", "arrived at", "delayed outside", "holding at"];
+const SEED_PLACES: [&str; 6] = ["Norfolk", "San Diego", "Honolulu", "Anchorage", "Denver", "Tampa"];
 
 pub fn debug(message: String) {
     event!(Level::DEBUG, "Thread {}: {}", thread_id::get(), message);
@@ -44,12 +125,26 @@ pub fn error(message: String) {
     event!(Level::ERROR, "Thread {}: {}", thread_id::get(), message);
 }
 
-fn build_test_claim() -> EdgeViewClaims {
+/*
+ * This function builds the claims carried by the test JWT.  An expired
+ * token was issued two hours ago and expired an hour ago.
+ */
+fn build_test_claim(expired: bool) -> EdgeViewClaims {
+    let now = jsonwebtoken::get_current_timestamp();
+    let hour = time::Duration::from_secs(3600).as_secs();
+    let (iat, exp) = if expired {
+        (now - 2 * hour, now - hour)
+    } else {
+        (now, now + hour)
+    };
+
     EdgeViewClaims {
-        exp:                    jsonwebtoken::get_current_timestamp() + time::Duration::from_secs(3600).as_secs(),
-        iat:                    jsonwebtoken::get_current_timestamp(),
+        exp,
+        iat,
         auth_time:              jsonwebtoken::get_current_timestamp(),
-        jti:                    String::from("e5f3e658-629a-42ff-a63f-20a50afa61d6"),
+        // Every token gets its own id, so that the server treats each
+        // connection as a distinct session.
+        jti:                    Uuid::new_v4().to_string(),
         iss:                    String::from("https://app.fmvedgeview.net/keycloak/auth/realms/fmv"),
         aud:                    None,
         sub:                    String::from("6e4b6e86-030b-41ed-90ab-c05325526a06"),
@@ -86,99 +181,706 @@ fn build_test_claim() -> EdgeViewClaims {
     }
 }
 
-pub fn build_users_request() -> String {
+pub fn build_users_request(domain: &str, room: &str) -> String {
     let get_users_request: GetUsersRequest = GetUsersRequest {
-        domain_id: String::from(TEST_DOMAIN),
-        room_name: String::from(TEST_ROOM)
+        domain_id: String::from(domain),
+        room_name: String::from(room)
     };
 
     serde_json::to_string(&get_users_request).unwrap()
 } // end build_users_request
 
-fn build_jwt(alg: Algorithm) -> String {
-    let header = Header::new(alg);
-    let claims = build_test_claim();
+pub fn build_messages_request(domain: &str, room: &str) -> String {
+    let messages_request: GetMessagesRequest = GetMessagesRequest {
+        domain_id: String::from(domain),
+        room_name: String::from(room),
+    };
+
+    serde_json::to_string(&messages_request).unwrap()
+} // end build_messages_request
+
+pub fn build_search_messages_request(
+    domain:             &str,
+    room:               &str,
+    keywords:           Option<&[String]>,
+    request_geo_tags:   bool,
+    look_back:          Option<time::Duration>,
+) -> String {
+    let keywords: Vec<String> = match keywords {
+        Some(keywords) => keywords.to_vec(),
+        None => vec!(String::from(DEFAULT_SEARCH_KEYWORD)),
+    };
+
+    event!(Level::DEBUG, "Searching for messages containing {}", keywords.join(", "));
+
+    let request: SearchMessagesRequest = SearchMessagesRequest {
+        domain_id: String::from(domain),
+        room_name: String::from(room),
+        keywords,
+        request_geo_tags: if request_geo_tags { Some(true) } else { None },
+        time_filter: look_back.map(TimeFilterRequest::from_look_back),
+    };
+
+    serde_json::to_string(&request).unwrap()
+} // end build_search_messages_request
+
+/*
+ * This function builds a Search Messages request with no keywords and no
+ * other filters, which the server should reject.
+ */
+pub fn build_empty_search_request(domain: &str, room: &str) -> String {
+    let request: SearchMessagesRequest = SearchMessagesRequest {
+        domain_id: String::from(domain),
+        room_name: String::from(room),
+        keywords: Vec::new(),
+        request_geo_tags: None,
+        time_filter: None,
+    };
+
+    serde_json::to_string(&request).unwrap()
+} // end build_empty_search_request
+
+/*
+ * This function builds a Send New Message request to the test room
+ * carrying the given text, marked with the given classification if any.
+ */
+pub fn build_new_message_request_with_text(
+    domain:         &str,
+    room:           &str,
+    text:           &str,
+    classification: Option<&str>,
+) -> String {
+    let request: SendNewMessageRequest = SendNewMessageRequest {
+        domain_id: String::from(domain),
+        room_name: String::from(room),
+        text: String::from(text),
+        classification: classification.map(String::from),
+    };
+
+    request.to_json()
+} // end build_new_message_request_with_text
+
+/*
+ * This function builds the key used to sign the JWT.  The HMAC algorithms
+ * use the shared test secret, or --jwt_secret if given; the asymmetric ones load a private key PEM
+ * from --jwt_private_key.
+ */
+fn build_encoding_key(args: &Args, alg: Algorithm) -> Result<EncodingKey, String> {
+    if let Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 = alg {
+        let secret = args.jwt_secret.as_deref().unwrap_or(JWT_SECRET);
+
+        return Ok(EncodingKey::from_secret(secret.as_ref()));
+    }
+
+    let path = args
+        .jwt_private_key
+        .as_ref()
+        .ok_or(format!("Signing with {:?} requires --jwt_private_key.", alg))?;
+
+    let pem = std::fs::read(path)
+        .map_err(|e| format!("Could not read the private key {}: {}", path, e))?;
+
+    let key = match alg {
+        Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(&pem),
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem),
+        _ => EncodingKey::from_rsa_pem(&pem),
+    };
+
+    key.map_err(|e| format!("The private key {} can't be used with {:?}: {}", path, alg, e))
+} // end build_encoding_key
+
+fn build_jwt(args: &Args, alg: Algorithm) -> Result<String, String> {
+    let mut header = Header::new(alg);
+
+    // Servers that rotate keys use the key ID to pick the verification key.
+    if let Some(kid) = &args.jwt_kid {
+        header.kid = Some(kid.clone());
+    }
+
+    if let Some(typ) = &args.jwt_typ {
+        header.typ = Some(typ.clone());
+    }
+
+    let claims = build_test_claim(args.jwt_expired);
 
     // Construct the JWT.
-    let jwt = encode(
+    encode(
         &header,
         &claims,
-        &EncodingKey::from_secret("MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAzq/jsj5MTmOA9sW4YBJpv16yLPvznKLj3UqNXQ17WhukP5wu6GQyHMUSqNV8CAqGEA8TJpoQcpTCs8iaKxpfF1yORKdeuvCa/aJZpOw6TwsJZa1OWLONyJnOuPeZZNDUn+D7as+tS9ws7UP3AtROO8hkMS7+B3C90eXTWhZnkzEDSfDmfUxPMvYH/5yGUI4AtzbAGPMwiDOXOguXUSkV5TP7RXTZqrgHp3yvzBsbaWtjW9r4tfzXRHuGFXhlEgBdsBIzupaXrpfqIjHQXDhJ1NnI6KOQUTDi5t3VOhfZ8z6WXMPdqi/pvyzTenAshvoTR2rEti6KyLqwTdW6y1KFVQIDAQAB".as_ref())).unwrap();
-
-    jwt
+        &build_encoding_key(args, alg)?)
+        .map_err(|e| format!("Could not sign the JWT: {}", e))
 } // end build_jwt
 
+/*
+ * This function resolves the server's host name, retrying with exponential
+ * backoff up to --dns_retries times when resolution fails.
+ */
+async fn resolve_host(args: &Args, host: &str, port: u16) -> Option<Vec<std::net::SocketAddr>> {
+    let mut backoff = time::Duration::from_millis(DNS_RETRY_INITIAL_BACKOFF_MS);
+    let mut attempt: u32 = 0;
+
+    loop {
+        match tokio::net::lookup_host((host, port)).await {
+            Ok(addresses) => {
+                let addresses: Vec<std::net::SocketAddr> = addresses.collect();
+
+                if !addresses.is_empty() {
+                    return Some(addresses);
+                }
+
+                error(format!("DNS resolution of {} returned no addresses.", host));
+            }
+            Err(e) => {
+                error(format!("DNS resolution of {} failed: {}", host, e));
+            }
+        }
+
+        if attempt >= args.dns_retries {
+            return None;
+        }
+
+        attempt += 1;
+        debug(format!("Retrying DNS resolution of {} in {} ms ({}/{}).",
+            host,
+            backoff.as_millis(),
+            attempt,
+            args.dns_retries));
+
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+} // end resolve_host
+
+/*
+ * This function formats a host for use in a URL, bracketing IPv6 literals
+ * as RFC 3986 requires.
+ */
+fn url_host(host: &str) -> String {
+    match host.parse::<std::net::Ipv6Addr>() {
+        Ok(_) => format!("[{}]", host),
+        Err(_) => String::from(host),
+    }
+}
+
+//==============================================================================
+// enum AuthMode
+//==============================================================================
+
+/// The AuthMode enumeration lists the places ws_connect can put the JWT in
+/// the upgrade request, chosen with --auth_mode.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+pub enum AuthMode {
+    // An "Authorization: Bearer" header.
+    Header,
+
+    // An access_token parameter in the query string, for servers that can't
+    // read headers during the upgrade.
+    Query,
+
+    // An access_token cookie, the way a browser would send it.
+    Cookie,
+}
+
+impl AuthMode {
+    /*
+     * This method gives the name of the header this mode puts the JWT in,
+     * if it uses one.
+     */
+    pub fn header_name(&self) -> Option<&'static str> {
+        match self {
+            AuthMode::Header => Some("Authorization"),
+            AuthMode::Cookie => Some("Cookie"),
+            AuthMode::Query => None,
+        }
+    }
+}
+
+//==============================================================================
+// enum ConnectError
+//==============================================================================
+
+/// The ConnectError enumeration describes why ws_connect could not open a
+/// connection, so that callers can tell an unreachable server apart from
+/// one that refused us.
+#[derive(Debug)]
+pub enum ConnectError {
+    // The upgrade request could not be built, for instance because the JWT
+    // could not be signed or the host is malformed.
+    Request(String),
+
+    // The server's host name could not be resolved.
+    Dns(String),
+
+    // The TLS session could not be set up, for instance because the
+    // server's certificate was not trusted.
+    Tls(String),
+
+    // The TCP connection could not be made.
+    Tcp(std::io::Error),
+
+    // The --proxy would not open a tunnel to the server.
+    Proxy(String),
+
+    // The server refused the WebSocket handshake.  The status, headers,
+    // and reason come from the HTTP response, when the server sent one.
+    Handshake {
+        status:     Option<StatusCode>,
+        headers:    HeaderMap,
+        reason:     String,
+    },
+
+    // The named phase of connecting did not finish in time.
+    Timeout {
+        phase:      &'static str,
+        elapsed_ms: u128,
+    },
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConnectError::Request(reason) => write!(f, "{}", reason),
+            ConnectError::Dns(host) => write!(f, "Could not resolve the server {}.", host),
+            ConnectError::Tcp(e) => write!(f, "Could not connect to the server: {}", e),
+            ConnectError::Proxy(reason) => write!(f, "{}", reason),
+            ConnectError::Tls(reason) => write!(f, "The TLS handshake with the server failed: {}", reason),
+            ConnectError::Handshake { status: Some(status), reason, .. } if reason.is_empty() => {
+                write!(f, "The server refused the WebSocket handshake with {}.", status)
+            }
+            ConnectError::Handshake { status: Some(status), reason, .. } => {
+                write!(f, "The server refused the WebSocket handshake with {}: {}", status, reason)
+            }
+            ConnectError::Handshake { status: None, reason, .. } => {
+                write!(f, "Could not complete the WebSocket handshake: {}", reason)
+            }
+            ConnectError::Timeout { phase, elapsed_ms } => {
+                write!(f, "Timed out in the {} phase after {} ms.", phase, elapsed_ms)
+            }
+        }
+    }
+}
+
+/*
+ * This function logs the status and headers of the server's answer to our
+ * upgrade request, which is often the only clue to why a server refused
+ * us.
+ */
+fn log_handshake_response(path: &str, status: StatusCode, headers: &HeaderMap) {
+    debug(format!("Handshake response from {}: {}", path, status));
+
+    for (name, value) in headers {
+        debug(format!("    {}: {}", name, String::from_utf8_lossy(value.as_bytes())));
+    }
+} // end log_handshake_response
+
+/*
+ * This function builds the connector used for --tls connections.  The
+ * server's certificate must chain to a system root or to --ca_cert, unless
+ * --insecure was given.  When HTTP/2 is to be tried, it is offered to the
+ * server with ALPN.
+ */
+fn build_tls_connector(args: &Args, http2: bool) -> Result<tokio_native_tls::TlsConnector, String> {
+    let mut builder = native_tls::TlsConnector::builder();
+
+    if http2 {
+        builder.request_alpns(&["h2", "http/1.1"]);
+    }
+
+    if args.insecure {
+        builder
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true);
+    }
+
+    if let Some(path) = &args.ca_cert {
+        let pem = std::fs::read(path)
+            .map_err(|e| format!("Could not read the CA certificate {}: {}", path, e))?;
+        let certificate = native_tls::Certificate::from_pem(&pem)
+            .map_err(|e| format!("The CA certificate {} is not a PEM certificate: {}", path, e))?;
+
+        builder.add_root_certificate(certificate);
+    }
+
+    builder
+        .build()
+        .map(tokio_native_tls::TlsConnector::from)
+        .map_err(|e| format!("Could not set up TLS: {}", e))
+} // end build_tls_connector
+
+/*
+ * This function opens a WebSocket connection to the given endpoint of the
+ * server, authenticating with a freshly signed JWT.  Failures are logged as
+ * well as returned.
+ */
 pub async fn ws_connect(
+    args:           &Args,
     server_port:    u16,
     jwt_alg:        Algorithm,
     path:           &str,
-) -> Option<WebSocketStream<TcpStream>> {
+) -> Result<ClientSocket, ConnectError> {
+    let result = try_ws_connect(args, server_port, jwt_alg, path, args.http2).await;
+
+    if let Err(e) = &result {
+        error(format!("{}: {}", path, e));
+    }
+
+    result
+} // end ws_connect
 
-    let url = ("localhost", server_port);
-    let auth_token: HeaderValue = format!("Bearer {}", build_jwt(jwt_alg)).parse().unwrap();
+/*
+ * This function builds the HTTP request that upgrades a connection to the
+ * given path to a WebSocket, carrying the JWT where --auth_mode puts it
+ * along with any --subprotocol and --header.
+ */
+fn build_upgrade_request(
+    args:           &Args,
+    server_port:    u16,
+    jwt:            &str,
+    path:           &str,
+) -> Result<Request, ConnectError> {
+    // In query mode the token goes on the end of the path, after any query
+    // the path already has.
+    let request_path = match args.auth_mode {
+        AuthMode::Query => {
+            let separator = if path.contains('?') { '&' } else { '?' };
+            format!("{}{}access_token={}", path, separator, jwt)
+        }
+        AuthMode::Header | AuthMode::Cookie => String::from(path),
+    };
 
-    let mut auth_request = format!("ws://localhost:{}{}",
+    let mut auth_request = format!("{}://{}:{}{}",
+            if args.tls { "wss" } else { "ws" },
+            url_host(&args.server_ip),
             server_port,
-            path)
+            request_path)
         .into_client_request()
-        .unwrap();
-    
-    event!(Level::TRACE, "Authorization header: {:?}", auth_token);
+        .map_err(|e| ConnectError::Request(format!("Could not build a request for server {}: {}",
+            args.server_ip,
+            e)))?;
+
+    let auth_header = match args.auth_mode {
+        AuthMode::Header => Some(format!("Bearer {}", jwt)),
+        AuthMode::Cookie => Some(format!("access_token={}", jwt)),
+        AuthMode::Query => None,
+    };
+
+    if let (Some(name), Some(token)) = (args.auth_mode.header_name(), auth_header) {
+        let auth_token: HeaderValue = token
+            .parse()
+            .map_err(|e| ConnectError::Request(format!("The JWT can't be used in a header: {}", e)))?;
+
+        event!(Level::TRACE, "{} header: {:?}", name, auth_token);
+
+        auth_request
+            .headers_mut()
+            .append(name, auth_token);
+    }
+
+    if let Some(subprotocol) = &args.subprotocol {
+        let value = subprotocol
+            .parse::<HeaderValue>()
+            .map_err(|e| ConnectError::Request(format!("Invalid subprotocol name {}: {}", subprotocol, e)))?;
+
+        auth_request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", value);
+    }
+
+    // The names and values were checked when the arguments were parsed.
+    for (name, value) in &args.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| ConnectError::Request(format!("Invalid header name {}: {}", name, e)))?;
+        let value = value
+            .parse::<HeaderValue>()
+            .map_err(|e| ConnectError::Request(format!("Invalid value for header {}: {}", name, e)))?;
+
+        auth_request
+            .headers_mut()
+            .append(name, value);
+    }
+
+    Ok(auth_request)
+} // end build_upgrade_request
+
+/*
+ * This function opens a WebSocket connection with an extended CONNECT over
+ * HTTP/2 when http2 is set and the server supports it, and with an HTTP/1.1
+ * Upgrade otherwise.
+ */
+async fn try_ws_connect(
+    args:           &Args,
+    server_port:    u16,
+    jwt_alg:        Algorithm,
+    path:           &str,
+    http2:          bool,
+) -> Result<ClientSocket, ConnectError> {
+
+    let url = (args.server_ip.as_str(), server_port);
+    let jwt = build_jwt(args, jwt_alg).map_err(ConnectError::Request)?;
+    let auth_request = build_upgrade_request(args, server_port, &jwt, path)?;
+
+    let proxy = args
+        .proxy
+        .as_deref()
+        .map(Proxy::parse)
+        .transpose()
+        .map_err(ConnectError::Request)?;
+
+    // Through a proxy, it's the proxy we resolve and connect to.
+    let (connect_host, connect_port) = match &proxy {
+        Some(proxy) => (proxy.host.as_str(), proxy.port),
+        None => url,
+    };
+
+    // Resolve the host separately so that name resolution failures can be
+    // told apart from the server refusing the connection.
+    let addresses = resolve_host(args, connect_host, connect_port)
+        .await
+        .ok_or(ConnectError::Dns(String::from(connect_host)))?;
+
+    // --connect_timeout_ms bounds the TCP connect and the handshake
+    // together, since a server can accept the socket but stall the upgrade.
+    let connect_started = tokio::time::Instant::now();
+    let connect_deadline = connect_started + time::Duration::from_millis(args.connect_timeout_ms);
+
+    let mut stream = match tokio::time::timeout_at(connect_deadline, TcpStream::connect(&addresses[..])).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => return Err(ConnectError::Tcp(e)),
+        Err(_) => {
+            return Err(ConnectError::Timeout {
+                phase:      "TCP connect",
+                elapsed_ms: connect_started.elapsed().as_millis(),
+            });
+        }
+    };
+
+    // --proxy_auth takes precedence over credentials in the proxy URL.
+    if let Some(proxy) = &proxy {
+        let target = format!("{}:{}", url_host(&args.server_ip), server_port);
+        let credentials = args.proxy_auth.as_deref().or(proxy.credentials.as_deref());
+
+        match tokio::time::timeout_at(connect_deadline, proxy.connect_tunnel(&mut stream, &target, credentials)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(reason)) => return Err(ConnectError::Proxy(reason)),
+            Err(_) => {
+                return Err(ConnectError::Timeout {
+                    phase:      "proxy CONNECT",
+                    elapsed_ms: connect_started.elapsed().as_millis(),
+                });
+            }
+        }
+    }
+
+    // The handshake gets its own limit when --handshake_timeout_ms is
+    // given, otherwise whatever is left of the connect timeout.  With
+    // --tls, the limit covers the TLS handshake as well.
+    let handshake_deadline = match args.handshake_timeout_ms {
+        Some(handshake_timeout_ms) => {
+            tokio::time::Instant::now() + time::Duration::from_millis(handshake_timeout_ms)
+        }
+        None => connect_deadline,
+    };
+
+    let stream = if args.tls {
+        let connector = build_tls_connector(args, http2).map_err(ConnectError::Request)?;
+
+        match tokio::time::timeout_at(handshake_deadline, connector.connect(&args.server_ip, stream)).await {
+            Ok(Ok(stream)) => MaybeTlsStream::NativeTls(stream),
+            Ok(Err(e)) => return Err(ConnectError::Tls(e.to_string())),
+            Err(_) => {
+                return Err(ConnectError::Timeout {
+                    phase:      "TLS handshake",
+                    elapsed_ms: connect_started.elapsed().as_millis(),
+                });
+            }
+        }
+    } else {
+        MaybeTlsStream::Plain(stream)
+    };
+
+    // The bytes are counted above TLS and HTTP/2, so the statistics show
+    // the WebSocket traffic rather than the stream carrying it.  They are
+    // only kept for the end of the run when they will be reported.
+    let new_stats = || if args.connection_stats {
+        ConnectionStats::register(path)
+    } else {
+        ConnectionStats::new(path)
+    };
+
+    let (socket, status, headers) = if http2 && http2::offers_http2(&stream) {
+        match tokio::time::timeout_at(handshake_deadline, http2::extended_connect(stream, &auth_request)).await {
+            Ok(Ok(Http2Connect::Answered { stream, status, headers })) => {
+                if !status.is_success() {
+                    log_handshake_response(path, status, &headers);
+
+                    return Err(ConnectError::Handshake {
+                        status:     Some(status),
+                        headers,
+                        reason:     String::new(),
+                    });
+                }
+
+                let stream = CountingStream::new(ClientStream::Http2(stream), new_stats());
+
+                (WebSocketStream::from_raw_socket(stream, Role::Client, None).await, status, headers)
+            }
+            Ok(Ok(Http2Connect::Unsupported(reason))) => {
+                debug(format!("Connecting to {} with HTTP/1.1 instead, since {}.", path, reason));
 
-    auth_request
-        .headers_mut()
-        .insert("Authorization", auth_token);
+                return Box::pin(try_ws_connect(args, server_port, jwt_alg, path, false)).await;
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(_) => {
+                return Err(ConnectError::Timeout {
+                    phase:      "HTTP/2 extended CONNECT",
+                    elapsed_ms: connect_started.elapsed().as_millis(),
+                });
+            }
+        }
+    } else {
+        if http2 {
+            debug(format!("The server did not choose HTTP/2 for {}, so HTTP/1.1 is used.", path));
+        }
+
+        let stream = CountingStream::new(ClientStream::Http1(stream), new_stats());
+
+        let (socket, response) = match tokio::time::timeout_at(handshake_deadline, client_async(auth_request, stream)).await {
+            Ok(Ok(connected)) => connected,
+            Ok(Err(TungsteniteError::Http(response))) => {
+                log_handshake_response(path, response.status(), response.headers());
+
+                return Err(ConnectError::Handshake {
+                    status:     Some(response.status()),
+                    headers:    response.headers().clone(),
+                    reason:     response
+                        .body()
+                        .as_deref()
+                        .map(|body| String::from_utf8_lossy(body).into_owned())
+                        .unwrap_or_default(),
+                });
+            }
+            Ok(Err(e)) => {
+                return Err(ConnectError::Handshake {
+                    status:     None,
+                    headers:    HeaderMap::new(),
+                    reason:     e.to_string(),
+                });
+            }
+            Err(_) => {
+                return Err(ConnectError::Timeout {
+                    phase:      "WebSocket handshake",
+                    elapsed_ms: connect_started.elapsed().as_millis(),
+                });
+            }
+        };
+
+        (socket, response.status(), response.headers().clone())
+    };
+
+    log_handshake_response(path, status, &headers);
+
+    // Make sure the server agreed to speak the subprotocol we asked for.
+    if let Some(subprotocol) = &args.subprotocol {
+        match headers.get("Sec-WebSocket-Protocol") {
+            Some(accepted) if accepted == subprotocol.as_str() => {
+                debug(format!("Server accepted subprotocol {}", subprotocol));
+            }
+            accepted => {
+                return Err(ConnectError::Handshake {
+                    status:     Some(status),
+                    headers:    headers.clone(),
+                    reason:     format!("Server did not accept subprotocol {}, responded with {:?}",
+                        subprotocol,
+                        accepted),
+                });
+            }
+        }
+    }
+
+    // Give the server time to finish setting up the session before the
+    // first request.  This papers over a server-side race where a request
+    // sent right after the upgrade can arrive before the server is ready to
+    // route it, so it defaults to no delay.
+    if args.post_connect_delay_ms > 0 {
+        tokio::time::sleep(time::Duration::from_millis(args.post_connect_delay_ms)).await;
+    }
 
-    match TcpStream::connect(url).await {
-        Ok(stream) => {
-            
-            let (socket, _) = client_async(
-                auth_request,
-                stream
-            ).await.expect("Failed to connect");
+    Ok(socket)
+} // end try_ws_connect
 
-            std::thread::sleep(time::Duration::from_millis(3000));
+/*
+ * This function builds the closing frame we send when finishing with a
+ * connection.  It is a normal close unless --close_code or --close_reason
+ * ask for something else.
+ */
+pub fn build_close_frame(args: &Args) -> CloseFrame<'static> {
+    CloseFrame {
+        code: CloseCode::from(args.close_code),
+        reason: std::borrow::Cow::Owned(args.close_reason.clone())
+    }
+} // end build_close_frame
 
-            Some(socket)
+/*
+ * This function closes a connection with the configured closing frame.
+ */
+pub async fn ws_close(args: &Args, mut socket: ClientSocket) {
+    match socket.close(Some(build_close_frame(args))).await {
+        Ok(()) => {
+            event!(Level::DEBUG, "Successfully sent the closing frame.");
         }
         Err(e) => {
-            error(format!("Could not connect to server: {}", e));
-            None
+            event!(Level::ERROR, "Could not send the closing frame: {}", e);
         }
     }
-} // end ws_connect
+} // end ws_close
 
-async fn ws_connect_send(
+pub async fn ws_connect_send(
+    args:           &Args,
     server_port:    u16,
     jwt_alg:        Algorithm,
     path:           &str,
     message:        String,
-) -> Option<Message> {
+) -> Result<Option<Message>, ConnectError> {
 
-    let socket = ws_connect(server_port, jwt_alg, path).await;
+    let socket = ws_connect(args, server_port, jwt_alg, path).await;
 
     match socket {
-        Some(socket) => {
+        Ok(mut socket) => {
+            let stats = socket.get_ref().stats();
+
+            if let Some(delay) = fault::slow_send(&args.fault) {
+                debug(format!("Fault injected: delaying the send by {} ms.", delay.as_millis()));
+                tokio::time::sleep(delay).await;
+            }
+
+            if args.fault.contains(&Fault::HalfFrame) {
+                debug(String::from("Fault injected: sending half of the request frame and dropping the connection."));
+
+                if let Err(e) = fault::send_half_frame(socket.get_mut(), &message).await {
+                    error(format!("Could not send the half frame: {}", e));
+                }
+                stats.record_request();
+                return Ok(None);
+            }
+
             let (mut write, mut read) = socket.split();
 
             // Send the request.
             let result = match write.send(Message::Text(message)).await {
                 Ok(()) => {
-                    event!(Level::DEBUG, "Attempting to read response from {} endpoint:", path);
-                    match read.next().await {
-                        Some(response) => {
-                            event!(Level::DEBUG, "We received a response!");
-        
-                            match response {
-                                Ok(payload) => Some(payload),
-                                Err(e) => {
-                                    event!(Level::ERROR, "{}", e);
-                                    None
-                                }
-                            }
-                        }
-                        None => None
+                    stats.record_request();
+
+                    if args.fault.contains(&Fault::DropAfterSend) {
+                        debug(String::from("Fault injected: dropping the connection after the send."));
+                        return Ok(None);
+                    }
+
+                    if let Some(delay) = fault::slow_read(&args.fault) {
+                        debug(format!("Fault injected: delaying the read by {} ms.", delay.as_millis()));
+                        tokio::time::sleep(delay).await;
                     }
+
+                    read_response(args, &mut read, path).await
                 }
                 Err(e) => {
                     event!(Level::ERROR, "Could not send the request: {}", e);
@@ -186,10 +888,7 @@ async fn ws_connect_send(
                 }
             };
         
-            let close_frame = CloseFrame {
-                code: CloseCode::Normal,
-                reason: std::borrow::Cow::Owned(String::from("Complete"))
-            };
+            let close_frame = build_close_frame(args);
         
             match write.send(Message::Close(Some(close_frame))).await {
                 Ok(()) => {
@@ -200,99 +899,1358 @@ async fn ws_connect_send(
                 }
             }
         
-            result
-        }
-        None => {
-            error(format!("No WebSocket connection."));
-            None
+            Ok(result)
         }
+        Err(e) => Err(e),
     }
 } // end ws_connect_send
 
-pub async fn spin_client(endpoint: String) {
+/*
+ * This function waits up to --response_timeout_ms for the response to a
+ * request that was just sent.
+ */
+pub async fn read_response<S>(args: &Args, read: &mut S, path: &str) -> Option<Message>
+where
+    S: futures_util::Stream<Item = Result<Message, TungsteniteError>> + Unpin
+{
+    event!(Level::DEBUG, "Attempting to read response from {} endpoint:", path);
+    let response_timeout = time::Duration::from_millis(args.response_timeout_ms);
 
-    match edge_view::client::ws_connect(
-        edge_view::client::SERVER_PORT,
-        Algorithm::HS256,
-        endpoint.as_str()
-    ).await {
-        Some(client) => {
-            event!(Level::DEBUG, "We successfully connected to the server!  Moving into the spin loop");
+    match tokio::time::timeout(response_timeout, read.next()).await {
+        Ok(Some(response)) => {
+            event!(Level::DEBUG, "We received a response!");
 
-            loop {
-                // We will stay here forever to keep the server connection
-                // live.
-                thread::sleep(time::Duration::from_secs(10));
-                debug(format!("spinning on {}", endpoint));
+            match response {
+                Ok(payload) => Some(payload),
+                Err(e) => {
+                    event!(Level::ERROR, "{}", e);
+                    None
+                }
             }
         }
-        None => {
-            error(format!("An error occurred connecting to the server. Killing the thread."));
+        Ok(None) => None,
+        Err(_) => {
+            error(format!("Timed out after {} ms waiting for a response from the {} endpoint.",
+                args.response_timeout_ms,
+                path));
+            None
         }
     }
-} // end spin_client
+} // end read_response
 
-pub async fn test_get_users() {
-    event!(Level::INFO, "Beginning Get Users Test.");
-
-    let response = ws_connect_send(
-        7878,
-        Algorithm::HS256,
-        "/users",
-        build_users_request()).await;
+/*
+ * This function gives the code and reason of a closing frame.  A Close
+ * message without a frame carries no status, which RFC 6455 reports as
+ * 1005.
+ */
+pub fn close_code_and_reason(frame: Option<&CloseFrame>) -> (CloseCode, String) {
+    match frame {
+        Some(frame) => (frame.code, frame.reason.to_string()),
+        None => (CloseCode::Status, String::new()),
+    }
+} // end close_code_and_reason
 
-    match response {
-        Some(payload) => {
+/*
+ * This function reads from a connection until the server closes it,
+ * skipping any other frames, and gives the code and reason it closed with.
+ * It gives None if the connection ends without a closing frame or nothing
+ * arrives within --response_timeout_ms.
+ */
+pub async fn read_until_close<S>(args: &Args, read: &mut S) -> Option<(CloseCode, String)>
+where
+    S: futures_util::Stream<Item = Result<Message, TungsteniteError>> + Unpin
+{
+    let response_timeout = time::Duration::from_millis(args.response_timeout_ms);
 
-            debug(format!("{}", payload));
-            event!(Level::INFO, "Get Users Test passed!");
-        }
-        None => {
-            event!(Level::DEBUG, "No response received.");
-            error(format!("Get Users Test Failed!"));
+    loop {
+        match tokio::time::timeout(response_timeout, read.next()).await {
+            Ok(Some(Ok(Message::Close(frame)))) => return Some(close_code_and_reason(frame.as_ref())),
+            Ok(Some(Ok(frame))) => {
+                debug(format!("Waiting for the server to close the connection; skipping {}", frame));
+            }
+            Ok(Some(Err(e))) => {
+                error(format!("An error occurred waiting for the server to close the connection: {}", e));
+                return None;
+            }
+            Ok(None) => return None,
+            Err(_) => {
+                error(format!("Timed out after {} ms waiting for the server to close the connection.",
+                    args.response_timeout_ms));
+                return None;
+            }
         }
     }
-} // end test_get_users
+} // end read_until_close
 
-pub async fn test_get_users_and_listen() {
-    event!(Level::INFO, "Beginning Get Users and Listen Test.");
+/*
+ * This function sends a request on a connection of its own and waits for
+ * the server to close that connection, giving the code and reason it closed
+ * with.  If the server doesn't close it, we do.
+ */
+pub async fn ws_connect_send_until_close(
+    args:           &Args,
+    server_port:    u16,
+    jwt_alg:        Algorithm,
+    path:           &str,
+    message:        String,
+) -> Result<Option<(CloseCode, String)>, ConnectError> {
+    let mut socket = ws_connect(args, server_port, jwt_alg, path).await?;
 
-    let socket = ws_connect(7878, Algorithm::HS256, "/users").await;
+    if let Err(e) = socket.send(Message::Text(message)).await {
+        error(format!("Could not send the request: {}", e));
+        return Ok(None);
+    }
 
-    if let Some(mut socket) = socket {
+    socket.get_ref().stats().record_request();
 
-        if let Ok(()) = socket.send(Message::Text(build_users_request())).await {
+    let close = read_until_close(args, &mut socket).await;
 
-            while let Some(update) = socket.next().await {
+    match close {
+        // Closing now flushes our reply to the server's closing frame.
+        Some(_) => {
+            let _ = socket.close(None).await;
+        }
+        None => ws_close(args, socket).await,
+    }
 
-                match update {
+    Ok(close)
+} // end ws_connect_send_until_close
 
-                    Ok(Message::Text(payload)) => {
-        
-                        event!(Level::DEBUG, "{}", payload);
-                    }
-                    Ok(Message::Close(_)) => {
-                        event!(Level::DEBUG,
-                            "{}: Received a Closing frame.",
-                            std::process::id()
-                        );
-                        break;
-                    }
-                    Ok(_) => {
-                        event!(Level::DEBUG,
-                            "{}: We received an unknown message. Ignoring.",
-                            std::process::id()
-                        );
-                    }
-                    Err(e) => {
-                        event!(Level::ERROR,
-                            "{}: An error occurred receiving from the WebSocket: {:#?}",
-                            std::process::id(),
-                            e
-                        );
-                    }
-                }
-            }
-        }
+/*
+ * This function finds the classification marking that applies to a payload:
+ * the top level "classification" field when there is one, otherwise the
+ * highest marking among the messages it carries.
+ */
+fn payload_classification(payload: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(payload).ok()?;
+
+    if let Some(marking) = value["classification"].as_str() {
+        return Some(String::from(marking));
+    }
+
+    value["messages"]
+        .as_array()?
+        .iter()
+        .filter_map(|message| message["classification"].as_str())
+        .max_by_key(|marking| ClassificationLevel::from_marking(marking))
+        .map(String::from)
+}
+
+/*
+ * This function prefixes a payload with a color-coded classification banner
+ * when --banners is set and stdout is a terminal.  Payloads without a
+ * marking are returned unchanged.
+ */
+pub fn with_banner(args: &Args, payload: &str) -> String {
+    if !args.banners || !std::io::stdout().is_terminal() {
+        return String::from(payload);
     }
-}
\ No newline at end of file
+
+    let marking = match payload_classification(payload) {
+        Some(marking) => marking,
+        None => return String::from(payload),
+    };
+
+    // Follow the conventional banner colors for each level.
+    let color = match ClassificationLevel::from_marking(&marking) {
+        Some(ClassificationLevel::UNCLASSIFIED) => "\x1b[30;42m",
+        Some(ClassificationLevel::CONFIDENTIAL) => "\x1b[97;44m",
+        Some(ClassificationLevel::SECRET)       => "\x1b[97;41m",
+        Some(ClassificationLevel::TOP_SECRET)   => "\x1b[30;48;5;208m",
+        None                                    => "\x1b[7m",
+    };
+
+    format!("{} {} \x1b[0m {}", color, marking, payload)
+} // end with_banner
+
+/// The PayloadCheck enumeration describes the outcome of the baseline
+/// checks that every response goes through before any type-specific
+/// validation is attempted.
+pub enum PayloadCheck {
+    // The raw bytes of the response are not valid UTF-8.
+    NotUtf8 { valid_up_to: usize },
+
+    // The response is text, but does not parse as a JSON document.
+    NotJson { offset: usize, error: serde_json::Error },
+
+    // The response nests arrays and objects deeper than MAX_JSON_DEPTH,
+    // which no legitimate response does.
+    TooDeep { offset: usize },
+
+    // The response is a well-formed JSON document.
+    Json    { text: String },
+}
+
+/*
+ * This function converts the line and column reported by serde_json
+ * into a byte offset within the given text.
+ */
+fn json_error_offset(text: &str, error: &serde_json::Error) -> usize {
+    let preceding_lines: usize = text
+        .split('\n')
+        .take(error.line().saturating_sub(1))
+        .map(|line| line.len() + 1)
+        .sum();
+
+    preceding_lines + error.column().saturating_sub(1)
+}
+
+/*
+ * This function scans a JSON text for the first byte at which arrays and
+ * objects are nested more than the given number of levels deep, without
+ * parsing it.
+ */
+fn json_depth_exceeded_at(text: &str, max_depth: usize) -> Option<usize> {
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (offset, byte) in text.bytes().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+
+                if depth > max_depth {
+                    return Some(offset);
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    None
+} // end json_depth_exceeded_at
+
+/*
+ * This function checks that a response is valid UTF-8 and parses as a
+ * JSON document, so that malformed responses can be told apart from
+ * responses that are well-formed but don't match the expected schema.
+ */
+pub fn check_payload(payload: &Message) -> PayloadCheck {
+    let bytes = payload.clone().into_data();
+
+    match String::from_utf8(bytes) {
+        Ok(text) => {
+            // Reject pathologically nested documents up front so that
+            // none of the validators have to cope with them.
+            if let Some(offset) = json_depth_exceeded_at(&text, MAX_JSON_DEPTH) {
+                return PayloadCheck::TooDeep { offset };
+            }
+
+            match serde_json::from_str::<serde_json::Value>(&text) {
+                Ok(_) => PayloadCheck::Json { text },
+                Err(e) => PayloadCheck::NotJson {
+                    offset: json_error_offset(&text, &e),
+                    error:  e,
+                },
+            }
+        }
+        Err(e) => PayloadCheck::NotUtf8 { valid_up_to: e.utf8_error().valid_up_to() },
+    }
+} // end check_payload
+
+/*
+ * This function builds a Send New Message request with varied text.  About
+ * a third of the messages include explicit coordinates, giving the server
+ * something to geo-tag.
+ */
+fn build_seed_message_request(
+    rng:            &mut StdRng,
+    index:          usize,
+    domain:         &str,
+    room:           &str,
+    classification: Option<&str>,
+) -> String {
+    let mut text = format!("[seed {}] {} {} {}",
+        index,
+        SEED_SUBJECTS[rng.gen_range(0..SEED_SUBJECTS.len())],
+        SEED_ACTIONS[rng.gen_range(0..SEED_ACTIONS.len())],
+        SEED_PLACES[rng.gen_range(0..SEED_PLACES.len())]);
+
+    if rng.gen_ratio(1, 3) {
+        text = format!("{} ({:.4}, {:.4})",
+            text,
+            rng.gen_range(-90.0..90.0),
+            rng.gen_range(-180.0..180.0));
+    }
+
+    build_new_message_request_with_text(domain, room, &text, classification)
+} // end build_seed_message_request
+
+/*
+ * This function populates the test room with generated messages through the
+ * send endpoint so that the read and search tests have data to work with.
+ * The sender and timestamp of each message are assigned by the server, so
+ * only the text varies.  The same seed always produces the same messages.
+ */
+pub async fn seed_room(args: &Args, count: usize) {
+    let seed: u64 = args.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut messages_sent: usize = 0;
+    let domain = args.domain();
+
+    event!(Level::INFO, "Seeding {} with {} messages using seed {}.", args.room(), count, seed);
+
+    let socket = match ws_connect(args, SERVER_PORT, Algorithm::HS256, "/send").await {
+        Ok(socket) => socket,
+        Err(_) => {
+            error(String::from("Could not connect to the server to seed the room."));
+            return;
+        }
+    };
+
+    let stats = socket.get_ref().stats();
+    let (mut write, mut read) = socket.split();
+
+    for index in 0..count {
+        match write.send(Message::Text(build_seed_message_request(&mut rng, index, &domain, args.room(), args.classification.as_deref()))).await {
+            Ok(()) => {
+                stats.record_request();
+
+                // read_response logs the error or timeout, so there is
+                // nothing more to report when no reply comes.
+                match read_response(args, &mut read, "/send").await {
+                    Some(Message::Close(frame)) => {
+                        let (code, reason) = close_code_and_reason(frame.as_ref());
+
+                        error(format!("The server closed the connection while seeding: {} {}", u16::from(code), reason));
+                        break;
+                    }
+                    Some(payload) => {
+                        debug(format!("{}", payload));
+                        messages_sent += 1;
+                    }
+                    None => {
+                        error(String::from("No reply to a seed message, so seeding stopped."));
+                        break;
+                    }
+                }
+            }
+            Err(e) => {
+                error(format!("Could not send a seed message: {}", e));
+                break;
+            }
+        }
+    }
+
+    let close_frame = build_close_frame(args);
+
+    if let Err(e) = write.send(Message::Close(Some(close_frame))).await {
+        error(format!("Could not send the closing frame: {}", e));
+    }
+
+    event!(Level::INFO, "Seeded {}/{} messages.", messages_sent, count);
+} // end seed_room
+
+/*
+ * This function builds the standard test request for the given endpoint,
+ * or returns None if we don't have one for it.
+ */
+pub fn build_request_for_path(args: &Args, path: &str) -> Option<String> {
+    match path {
+        "/messages" => Some(build_messages_request(&args.domain(), args.room())),
+        "/search"   => Some(build_search_messages_request(&args.domain(), args.room(), args.search_keywords.as_deref(), false, args.look_back)),
+        "/send"     => Some(build_new_message_request_with_text(
+            &args.domain(),
+            args.room(),
+            args.message_text(),
+            args.classification.as_deref())),
+        "/users"    => Some(build_users_request(&args.domain(), args.room())),
+        _ => None,
+    }
+} // end build_request_for_path
+
+/*
+ * This function completes once the given time has passed since the given
+ * instant, or never when no limit is given.
+ */
+async fn silence_watchdog(from: time::Instant, limit: Option<time::Duration>) {
+    match limit {
+        Some(limit) => tokio::time::sleep_until(tokio::time::Instant::from_std(from + limit)).await,
+        None => std::future::pending().await,
+    }
+} // end silence_watchdog
+
+/*
+ * This function connects to the given endpoint and sends it the request,
+ * returning the socket the server's frames will arrive on.
+ */
+async fn connect_and_send(args: &Args, path: &str, request: &str) -> Option<ClientSocket> {
+    let mut socket = ws_connect(args, SERVER_PORT, Algorithm::HS256, path).await.ok()?;
+
+    if let Err(e) = socket.send(Message::Text(String::from(request))).await {
+        error(format!("Could not send the request to {}: {}", path, e));
+        return None;
+    }
+    socket.get_ref().stats().record_request();
+
+    Some(socket)
+} // end connect_and_send
+
+/*
+ * This function sends a request to the given endpoint and hands each text
+ * frame the server pushes back to on_frame, until the server closes the
+ * stream, on_frame returns false, no frame arrives for idle_timeout, or a
+ * shutdown is requested.  With --dedup_listen, a frame identical to the
+ * one before it is left out.  With --max_silence_secs, a stalled stream is
+ * reported, and with --reconnect_on_silence it is replaced by a new
+ * connection.  It returns false if the connection could not be made or
+ * failed along the way.
+ */
+async fn receive_frames<F>(
+    args:           &Args,
+    path:           &str,
+    request:        &str,
+    idle_timeout:   Option<time::Duration>,
+    mut on_frame:   F,
+) -> bool where F: FnMut(String) -> bool {
+    let mut socket = match connect_and_send(args, path, request).await {
+        Some(socket) => socket,
+        None => return false,
+    };
+    let max_silence = args.max_silence_secs.map(time::Duration::from_secs);
+
+    // When the last data frame arrived, and the longest gap between data
+    // frames so far.  Pings and pongs don't count as activity.
+    let mut last_data_frame = time::Instant::now();
+    let mut watchdog_from = last_data_frame;
+    let mut longest_silence = time::Duration::ZERO;
+    let mut reconnects: u32 = 0;
+
+    // The last frame we handled and how many identical frames have
+    // followed it, used when deduplication is requested.
+    let mut last_frame: Option<serde_json::Value> = None;
+    let mut frames_received: u32 = 0;
+    let mut duplicates_suppressed: u32 = 0;
+
+    // Whether we are the ones ending an open connection, and whether it
+    // failed before it ended.
+    let mut close_when_done = false;
+    let mut failed = false;
+
+    loop {
+        let update = tokio::select! {
+            update = socket.next() => update,
+            _ = shutdown::requested() => {
+                if let Err(e) = socket.close(Some(shutdown::close_frame())).await {
+                    error(format!("Could not close the connection: {}", e));
+                }
+                break;
+            }
+            _ = silence_watchdog(last_data_frame, idle_timeout) => {
+                debug(format!("No frames from {} for {} ms; done listening.",
+                    path,
+                    last_data_frame.elapsed().as_millis()));
+                close_when_done = true;
+                break;
+            }
+            _ = silence_watchdog(watchdog_from, max_silence) => {
+                event!(Level::WARN,
+                    "No frames received for {} seconds; the stream may have stalled.",
+                    last_data_frame.elapsed().as_secs()
+                );
+                watchdog_from = time::Instant::now();
+
+                if args.reconnect_on_silence {
+                    // Tell the server we are done with the stalled
+                    // connection, but don't wait on it for long.
+                    let close_timeout = time::Duration::from_millis(STALLED_CLOSE_TIMEOUT_MS);
+
+                    if tokio::time::timeout(close_timeout, socket.close(Some(build_close_frame(args)))).await.is_err() {
+                        debug(String::from("Timed out closing the stalled connection."));
+                    }
+
+                    match connect_and_send(args, path, request).await {
+                        Some(new_socket) => {
+                            debug(String::from("Reconnected after the stream went silent."));
+                            socket = new_socket;
+                            reconnects += 1;
+                            watchdog_from = time::Instant::now();
+                        }
+                        None => {
+                            error(String::from("Could not reconnect after the stream went silent."));
+                            failed = true;
+                            break;
+                        }
+                    }
+                }
+                continue;
+            }
+        };
+
+        if let Some(Ok(Message::Text(_))) | Some(Ok(Message::Binary(_))) = &update {
+            longest_silence = longest_silence.max(last_data_frame.elapsed());
+            last_data_frame = time::Instant::now();
+            watchdog_from = last_data_frame;
+        }
+
+        match update {
+            Some(Ok(Message::Text(payload))) => {
+                frames_received += 1;
+
+                if args.dedup_listen {
+                    // Compare the frames structurally so that differences
+                    // in whitespace or key order don't defeat the
+                    // deduplication.
+                    let frame = serde_json::from_str::<serde_json::Value>(&payload)
+                        .unwrap_or_else(|_| serde_json::Value::String(payload.clone()));
+
+                    if last_frame.as_ref() == Some(&frame) {
+                        duplicates_suppressed += 1;
+
+                        if duplicates_suppressed.is_multiple_of(DUPLICATE_SUMMARY_INTERVAL) {
+                            debug(format!("{} duplicate frames suppressed so far.",
+                                duplicates_suppressed));
+                        }
+                        continue;
+                    }
+
+                    last_frame = Some(frame);
+                }
+
+                event!(Level::DEBUG, "{}", with_banner(args, &payload));
+
+                if !on_frame(payload) {
+                    close_when_done = true;
+                    break;
+                }
+            }
+            Some(Ok(Message::Close(frame))) => {
+                let (code, reason) = close_code_and_reason(frame.as_ref());
+
+                event!(Level::INFO,
+                    "{} closed the connection with {} ({:?}): {}",
+                    path,
+                    u16::from(code),
+                    code,
+                    reason
+                );
+                break;
+            }
+            None => {
+                debug(format!("{} closed the stream.", path));
+                break;
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => {
+                error(format!("An error occurred receiving from {}: {}", path, e));
+                failed = true;
+                break;
+            }
+        }
+    }
+
+    if close_when_done {
+        ws_close(args, socket).await;
+    }
+
+    if args.dedup_listen {
+        event!(Level::INFO,
+            "Received {} frames, {} duplicates suppressed.",
+            frames_received,
+            duplicates_suppressed
+        );
+    }
+
+    if max_silence.is_some() {
+        longest_silence = longest_silence.max(last_data_frame.elapsed());
+
+        event!(Level::INFO,
+            "Longest silence between frames: {} ms, {} reconnects.",
+            longest_silence.as_millis(),
+            reconnects
+        );
+    }
+
+    !failed
+} // end receive_frames
+
+//==============================================================================
+// struct ListenResult
+//==============================================================================
+
+/// The ListenResult structure holds the frames listen collected and whether
+/// the listening passed: the connection held up, every frame parsed as the
+/// endpoint's response type and none was an Error, and no classification
+/// order violations were found.
+pub struct ListenResult {
+    pub messages:       Vec<String>,
+    pub passed:         bool,
+}
+
+/*
+ * This function sends a request to the given endpoint and collects the
+ * text frames the server pushes back until it sends a Close frame,
+ * max_messages frames have arrived, or no frame arrives for idle_timeout.
+ * Each frame is parsed as the endpoint's response type, and any that isn't
+ * one, or is an Error, is logged.  With --strict_classification_order, the
+ * messages in those frames are also checked for markings that drop within
+ * a thread.
+ */
+pub async fn listen(
+    args:           &Args,
+    path:           &str,
+    request:        String,
+    max_messages:   Option<usize>,
+    idle_timeout:   time::Duration,
+) -> ListenResult {
+    let mut messages: Vec<String> = Vec::new();
+    let mut bad_frames: usize = 0;
+    let mut classification_order = ClassificationOrder::new();
+
+    let received = receive_frames(args, path, &request, Some(idle_timeout), |payload| {
+        match EdgeViewResponseTypes::try_from_json(path, payload.clone()) {
+            Ok(EdgeViewResponseTypes::Error { response }) => {
+                error(format!("{} sent an error: {} {}", path, response.code, response.message));
+                bad_frames += 1;
+            }
+            Ok(response) => event!(Level::DEBUG, "{} sent {}", path, response),
+            Err(e) => {
+                error(e);
+                bad_frames += 1;
+            }
+        }
+
+        if args.strict_classification_order {
+            classification_order.check_payload(&payload);
+        }
+
+        messages.push(payload);
+        max_messages.is_none_or(|max_messages| messages.len() < max_messages)
+    }).await;
+
+    if args.strict_classification_order {
+        event!(Level::INFO,
+            "Classification order violations: {}",
+            classification_order.violations()
+        );
+    }
+
+    ListenResult {
+        passed:     received && bad_frames == 0 && classification_order.violations() == 0,
+        messages,
+    }
+} // end listen
+
+/*
+ * This function builds the search that --search_all pages through: the
+ * --search_keywords in the test room, between --search_start and
+ * --search_end if given, oldest first so that the cursor walks the results
+ * in a stable order.
+ */
+pub fn build_paged_search_request(args: &Args) -> Result<SearchChatMessagesRequest, String> {
+    let keywords: Vec<String> = match &args.search_keywords {
+        Some(keywords) => keywords.clone(),
+        None => vec!(String::from(DEFAULT_SEARCH_KEYWORD)),
+    };
+
+    let mut builder = SearchChatMessagesRequest::builder()
+        .keywords(keywords)
+        .room_filter(args.domain(), vec!(String::from(args.room())))
+        .sort(SortField::TIME, SortDirection::ASC)
+        .limit(SEARCH_ALL_PAGE_SIZE);
+
+    if let (Some(start), Some(end)) = (&args.search_start, &args.search_end) {
+        builder = builder.time_range(start.clone(), end.clone());
+    }
+
+    builder.build()
+} // end build_paged_search_request
+
+/*
+ * This function sets the request's cursor to the one a page of search
+ * results handed back, and tells whether there is another page to ask for.
+ * The last page either has no cursor or hands back the cursor it was asked
+ * for.
+ */
+fn advance_cursor(request: &mut SearchChatMessagesRequest, next_cursor_mark: Option<String>) -> bool {
+    match next_cursor_mark {
+        Some(cursor) if request.cursor.as_ref() != Some(&cursor) => {
+            request.cursor = Some(cursor);
+            true
+        }
+        _ => false,
+    }
+} // end advance_cursor
+
+/*
+ * This function runs a search and follows its cursor from page to page
+ * over one connection, stopping when the server has no more pages or after
+ * max_pages pages, and returns the messages from every page in order.
+ */
+pub async fn search_all(
+    args:           &Args,
+    path:           &str,
+    base_request:   SearchChatMessagesRequest,
+    max_pages:      usize,
+) -> Vec<ChatMessageSchema> {
+    let mut messages: Vec<ChatMessageSchema> = Vec::new();
+    let mut request = base_request;
+
+    let mut socket = match ws_connect(args, SERVER_PORT, Algorithm::HS256, path).await {
+        Ok(socket) => socket,
+        Err(_) => {
+            return messages;
+        }
+    };
+
+    let stats = socket.get_ref().stats();
+    let response_timeout = time::Duration::from_millis(args.response_timeout_ms);
+
+    for page in 1..=max_pages {
+        if let Err(e) = socket.send(Message::Text(request.to_json())).await {
+            error(format!("Could not request page {} from {}: {}", page, path, e));
+            break;
+        }
+        stats.record_request();
+
+        let payload = match tokio::time::timeout(response_timeout, socket.next()).await {
+            Ok(Some(Ok(Message::Text(payload)))) => payload,
+            Ok(Some(Ok(other))) => {
+                error(format!("Expected page {} from {}, got {:?}.", page, path, other));
+                break;
+            }
+            Ok(Some(Err(e))) => {
+                error(format!("An error occurred receiving page {} from {}: {}", page, path, e));
+                break;
+            }
+            Ok(None) => {
+                error(format!("{} closed the connection before sending page {}.", path, page));
+                break;
+            }
+            Err(_) => {
+                error(format!("Timed out waiting for page {} from {}.", page, path));
+                break;
+            }
+        };
+
+        let response = match serde_json::from_str::<SearchChatMessagesResponse>(&payload) {
+            Ok(response) => response,
+            Err(e) => {
+                error(format!("Page {} is not a SearchChatMessagesResponse: {}", page, e));
+                break;
+            }
+        };
+
+        let page_messages = response.messages.unwrap_or_default();
+        debug(format!("Page {} held {} messages.", page, page_messages.len()));
+        messages.extend(page_messages);
+
+        if !advance_cursor(&mut request, response.nextCursorMark) {
+            break;
+        }
+
+        if page == max_pages {
+            debug(format!("Stopped after {} pages with more results remaining.", max_pages));
+        }
+    }
+
+    ws_close(args, socket).await;
+
+    messages
+} // end search_all
+
+//==============================================================================
+// struct PipelineTally
+//==============================================================================
+
+/// The PipelineTally structure counts the responses pipeline_test has
+/// matched to their requests, and those it couldn't.
+#[derive(Default)]
+struct PipelineTally {
+    matched:        usize,
+    reordered:      usize,
+    unmatched:      usize,
+
+    // The position the next response should have been sent in.
+    next_expected:  usize,
+}
+
+/*
+ * This function waits for the next response to a pipelined request and
+ * matches it to its request by correlation id.  It returns false once no
+ * more responses will come.
+ */
+async fn receive_pipelined<S>(
+    read:       &mut S,
+    pending:    &mut HashMap<String, usize>,
+    tally:      &mut PipelineTally,
+) -> bool
+where
+    S: futures_util::Stream<Item = Result<Message, TungsteniteError>> + Unpin
+{
+    let response = tokio::time::timeout(
+        time::Duration::from_secs(PIPELINE_RESPONSE_SECS),
+        read.next()).await;
+
+    let payload = match response {
+        Ok(Some(Ok(Message::Text(payload)))) => payload,
+        Ok(Some(Ok(_))) => return true,
+        Ok(Some(Err(e))) => {
+            error(format!("{}", e));
+            return false;
+        }
+        Ok(None) => {
+            error(String::from("The server closed the connection during the pipeline."));
+            return false;
+        }
+        Err(_) => {
+            error(format!("Gave up waiting for {} responses.", pending.len()));
+            return false;
+        }
+    };
+
+    debug(payload.clone());
+
+    let correlation_id = serde_json::from_str::<serde_json::Value>(&payload)
+        .ok()
+        .and_then(|response| response[CORRELATION_ID_FIELD].as_str().map(String::from));
+
+    match correlation_id.and_then(|correlation_id| pending.remove(&correlation_id)) {
+        Some(index) => {
+            tally.matched += 1;
+
+            if index != tally.next_expected {
+                debug(format!("The response to request {} arrived in position {}.",
+                    index,
+                    tally.next_expected));
+                tally.reordered += 1;
+            }
+            tally.next_expected += 1;
+        }
+        None => {
+            error(format!("Could not match a response to any request: {}", payload));
+            tally.unmatched += 1;
+        }
+    }
+
+    true
+} // end receive_pipelined
+
+/*
+ * This function sends several Get Users requests back-to-back on one
+ * connection without waiting for responses, each tagged with a correlation
+ * id, then matches the responses to their requests by that id.  Responses
+ * that arrive out of order, and requests or responses that can't be
+ * matched, are reported.  With --max_inflight, sending pauses whenever that
+ * many requests are still waiting for responses, and how often it did is
+ * reported.  It returns whether every request was matched to a response.
+ */
+pub async fn pipeline_test(args: &Args, count: usize) -> bool {
+    event!(Level::INFO, "Beginning Pipeline Test with {} requests.", count);
+
+    let socket = match ws_connect(args, SERVER_PORT, Algorithm::HS256, "/users").await {
+        Ok(socket) => socket,
+        Err(_) => {
+            return false;
+        }
+    };
+
+    let stats = socket.get_ref().stats();
+    let (mut write, mut read) = socket.split();
+
+    // Map each correlation id to the position its request was sent in.
+    let mut pending: HashMap<String, usize> = HashMap::new();
+    let mut tally = PipelineTally::default();
+    let mut requests_sent: usize = 0;
+
+    // Whether responses can still arrive, and how many times sending had
+    // to wait for them under --max_inflight.
+    let mut receiving = true;
+    let mut backpressure_engaged: usize = 0;
+    let max_inflight = args.max_inflight.map(|max_inflight| max_inflight as usize);
+
+    for index in 0..count {
+        if max_inflight.is_some_and(|max_inflight| pending.len() >= max_inflight) {
+            backpressure_engaged += 1;
+
+            while receiving && max_inflight.is_some_and(|max_inflight| pending.len() >= max_inflight) {
+                receiving = receive_pipelined(&mut read, &mut pending, &mut tally).await;
+            }
+
+            if !receiving {
+                break;
+            }
+        }
+
+        let correlation_id = Uuid::new_v4().to_string();
+
+        let mut request: serde_json::Value = serde_json::from_str(&build_users_request(&args.domain(), args.room())).unwrap();
+        request[CORRELATION_ID_FIELD] = serde_json::Value::String(correlation_id.clone());
+
+        match write.send(Message::Text(request.to_string())).await {
+            Ok(()) => {
+                stats.record_request();
+                pending.insert(correlation_id, index);
+                requests_sent += 1;
+            }
+            Err(e) => {
+                error(format!("Could not send pipelined request {}: {}", index, e));
+            }
+        }
+    }
+
+    while receiving && !pending.is_empty() {
+        receiving = receive_pipelined(&mut read, &mut pending, &mut tally).await;
+    }
+
+    for (correlation_id, index) in &pending {
+        error(format!("No response to request {} ({} {}).",
+            index,
+            CORRELATION_ID_FIELD,
+            correlation_id));
+    }
+
+    event!(Level::INFO,
+        "Pipeline: {}/{} requests matched, {} out of order, {} unmatched responses.",
+        tally.matched,
+        requests_sent,
+        tally.reordered,
+        tally.unmatched
+    );
+
+    if let Some(max_inflight) = max_inflight {
+        event!(Level::INFO,
+            "Pipeline: sending paused {} times to keep at most {} requests in flight.",
+            backpressure_engaged,
+            max_inflight
+        );
+    }
+
+    let passed = pending.is_empty() && tally.unmatched == 0 && requests_sent == count;
+
+    if passed {
+        event!(Level::INFO, "Pipeline Test passed!");
+    } else {
+        error(String::from("Pipeline Test Failed!"));
+    }
+
+    let close_frame = build_close_frame(args);
+
+    if let Err(e) = write.send(Message::Close(Some(close_frame))).await {
+        error(format!("Could not send the closing frame: {}", e));
+    }
+
+    passed
+} // end pipeline_test
+
+/*
+ * This function opens a single connection to the given endpoint, then sends
+ * each line read from stdin as a request and prints whatever the server
+ * sends back, until stdin reaches EOF.  Lines that aren't valid JSON are
+ * reported and skipped.  The connection is kept alive with periodic pings
+ * while we wait for input.
+ */
+pub async fn interactive(args: &Args, path: &str) {
+    let socket = match ws_connect(args, SERVER_PORT, Algorithm::HS256, path).await {
+        Ok(socket) => socket,
+        Err(_) => {
+            error(format!("Could not connect to {} for interactive mode.", path));
+            return;
+        }
+    };
+
+    let stats = socket.get_ref().stats();
+    let (mut write, mut read) = socket.split();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut heartbeat = tokio::time::interval(time::Duration::from_secs(INTERACTIVE_HEARTBEAT_SECS));
+    let mut pending_responses: usize = 0;
+
+    println!("Connected to {}.  Enter one JSON request per line, EOF to finish.", path);
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                match line {
+                    Ok(Some(line)) => {
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+
+                        if let Err(e) = serde_json::from_str::<serde_json::Value>(&line) {
+                            println!("Not sent, the line is not valid JSON: {}", e);
+                            continue;
+                        }
+
+                        match write.send(Message::Text(line)).await {
+                            Ok(()) => {
+                                stats.record_request();
+                                pending_responses += 1;
+                            }
+                            Err(e) => {
+                                error(format!("Could not send the request: {}", e));
+                                break;
+                            }
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        error(format!("Could not read from stdin: {}", e));
+                        break;
+                    }
+                }
+            }
+            frame = read.next() => {
+                match frame {
+                    Some(Ok(Message::Text(payload))) => {
+                        println!("{}", with_banner(args, &payload));
+                        pending_responses = pending_responses.saturating_sub(1);
+                    }
+                    Some(Ok(Message::Close(close_frame))) => {
+                        println!("The server closed the connection: {:?}", close_frame);
+                        return;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        error(format!("An error occurred receiving from the WebSocket: {}", e));
+                        break;
+                    }
+                    None => return,
+                }
+            }
+            _ = heartbeat.tick() => {
+                if let Err(e) = write.send(Message::Ping(Vec::new())).await {
+                    error(format!("Could not send a heartbeat ping: {}", e));
+                    break;
+                }
+            }
+            _ = shutdown::requested() => break,
+        }
+    }
+
+    // When stdin is piped in, EOF can arrive before the responses to the
+    // last few requests, so give them a chance to arrive.
+    while pending_responses > 0 {
+        match tokio::time::timeout(time::Duration::from_secs(INTERACTIVE_DRAIN_SECS), read.next()).await {
+            Ok(Some(Ok(Message::Text(payload)))) => {
+                println!("{}", with_banner(args, &payload));
+                pending_responses -= 1;
+            }
+            Ok(Some(Ok(Message::Close(_)))) | Ok(Some(Err(_))) | Ok(None) => return,
+            Ok(Some(Ok(_))) => {}
+            Err(_) => {
+                error(format!("Gave up waiting for {} responses.", pending_responses));
+                break;
+            }
+        }
+    }
+
+    let close_frame = build_close_frame(args);
+
+    if let Err(e) = write.send(Message::Close(Some(close_frame))).await {
+        error(format!("Could not send the closing frame: {}", e));
+    }
+} // end interactive
+
+pub async fn spin_client(args: Args, endpoint: String) {
+
+    match edge_view::client::ws_connect(
+        &args,
+        edge_view::client::SERVER_PORT,
+        Algorithm::HS256,
+        endpoint.as_str()
+    ).await {
+        Ok(mut client) => {
+            event!(Level::DEBUG, "We successfully connected to the server!  Moving into the spin loop");
+
+            let ping_interval = time::Duration::from_secs(args.ping_interval_secs);
+            let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+
+            // We will stay here until the server closes the connection,
+            // draining whatever it pushes to us and pinging it so that it
+            // and any proxies in between don't reap the idle connection.
+            loop {
+                tokio::select! {
+                    frame = client.next() => {
+                        match frame {
+                            Some(Ok(Message::Text(payload))) => {
+                                event!(Level::DEBUG, "{}: {}", endpoint, with_banner(&args, &payload));
+                            }
+                            Some(Ok(Message::Pong(_))) => {
+                                debug(format!("spinning on {}", endpoint));
+                            }
+                            Some(Ok(Message::Close(frame))) => {
+                                let (code, reason) = close_code_and_reason(frame.as_ref());
+
+                                event!(Level::INFO, "{} closed the connection with {} ({:?}): {}",
+                                    endpoint,
+                                    u16::from(code),
+                                    code,
+                                    reason);
+                                break;
+                            }
+                            Some(Ok(frame)) => {
+                                debug(format!("{} sent {:?}", endpoint, frame));
+                            }
+                            Some(Err(e)) => {
+                                error(format!("An error occurred receiving from {}: {}", endpoint, e));
+                                break;
+                            }
+                            None => {
+                                error(format!("{} closed the connection.", endpoint));
+                                break;
+                            }
+                        }
+                    }
+                    _ = heartbeat.tick() => {
+                        if let Err(e) = client.send(Message::Ping(Vec::new())).await {
+                            error(format!("Could not ping {}: {}", endpoint, e));
+                            break;
+                        }
+                    }
+                    _ = shutdown::requested() => {
+                        if let Err(e) = client.close(Some(shutdown::close_frame())).await {
+                            error(format!("Could not close the connection to {}: {}", endpoint, e));
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            error(format!("An error occurred connecting to the server ({}). Killing the thread.", e));
+        }
+    }
+} // end spin_client
+
+pub async fn test_get_users_and_listen(args: Args) {
+    event!(Level::INFO, "Beginning Get Users and Listen Test.");
+
+    let request = build_users_request(&args.domain(), args.room());
+
+    receive_frames(&args, "/users", &request, None, |_| true).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    // Parses the arguments as if they were given on the command line.
+    fn args(arguments: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("WebSocket-TestClient").chain(arguments.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn jwt_header_carries_kid_and_typ() {
+        let jwt = build_jwt(&args(&["--jwt_kid", "edge-view-2024", "--jwt_typ", "at+jwt"]), Algorithm::HS256).unwrap();
+        let header = jsonwebtoken::decode_header(&jwt).unwrap();
+
+        assert_eq!(header.alg, Algorithm::HS256);
+        assert_eq!(header.kid.as_deref(), Some("edge-view-2024"));
+        assert_eq!(header.typ.as_deref(), Some("at+jwt"));
+    }
+
+    #[test]
+    fn jwt_header_defaults_without_kid_or_typ() {
+        let jwt = build_jwt(&args(&[]), Algorithm::HS256).unwrap();
+        let header = jsonwebtoken::decode_header(&jwt).unwrap();
+
+        assert_eq!(header.kid, None);
+        assert_eq!(header.typ.as_deref(), Some("JWT"));
+    }
+
+    #[test]
+    fn nested_payload_is_too_deep() {
+        let nested = format!("{}{}", "[".repeat(MAX_JSON_DEPTH + 1), "]".repeat(MAX_JSON_DEPTH + 1));
+
+        match check_payload(&Message::text(nested)) {
+            PayloadCheck::TooDeep { offset } => assert_eq!(offset, MAX_JSON_DEPTH),
+            _ => panic!("A payload nested past MAX_JSON_DEPTH was not rejected."),
+        }
+    }
+
+    #[test]
+    fn nested_payload_at_the_limit_is_json() {
+        let nested = format!("{}{}", "[".repeat(MAX_JSON_DEPTH), "]".repeat(MAX_JSON_DEPTH));
+
+        assert!(matches!(check_payload(&Message::text(nested)), PayloadCheck::Json { .. }));
+    }
+
+    #[test]
+    fn brackets_inside_strings_do_not_count_towards_depth() {
+        assert_eq!(json_depth_exceeded_at(r#"{"text": "[[[[\"{{{{"}"#, 1), None);
+        assert_eq!(json_depth_exceeded_at(r#"{"a": [{"b": 1}]}"#, 2), Some(7));
+    }
+
+    #[test]
+    fn close_frame_carries_close_code_and_reason() {
+        let frame = build_close_frame(&args(&["--close_code", "1008", "--close_reason", "Policy test"]));
+
+        assert_eq!(frame.code, CloseCode::Policy);
+        assert_eq!(frame.reason, "Policy test");
+    }
+
+    #[tokio::test]
+    async fn ws_close_sends_the_configured_close_code() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            match socket.next().await {
+                Some(Ok(Message::Close(Some(frame)))) => (frame.code, frame.reason.into_owned()),
+                other => panic!("Expected a closing frame, got {:?}", other),
+            }
+        });
+
+        let args = args(&["--server_ip", "127.0.0.1", "--close_code", "4000", "--close_reason", "Custom"]);
+        let socket = ws_connect(&args, port, Algorithm::HS256, "/messages").await.unwrap();
+
+        ws_close(&args, socket).await;
+
+        assert_eq!(server.await.unwrap(), (CloseCode::from(4000), String::from("Custom")));
+    }
+
+    #[test]
+    fn upgrade_request_goes_to_the_server_ip() {
+        let request = build_upgrade_request(&args(&["--server_ip", "edge-view.example.com"]), 8443, "token", "/messages").unwrap();
+
+        assert_eq!(request.uri().host(), Some("edge-view.example.com"));
+        assert_eq!(request.uri().port_u16(), Some(8443));
+        assert_eq!(request.uri().path(), "/messages");
+    }
+
+    #[test]
+    fn upgrade_request_brackets_an_ipv6_server_ip() {
+        let request = build_upgrade_request(&args(&["--server_ip", "fd00::1"]), 8443, "token", "/messages").unwrap();
+
+        assert_eq!(request.uri().to_string(), "ws://[fd00::1]:8443/messages");
+    }
+
+    #[test]
+    fn upgrade_request_to_an_invalid_host_is_an_error() {
+        let result = build_upgrade_request(&args(&["--server_ip", "not a host"]), 8443, "token", "/messages");
+
+        assert!(matches!(result, Err(ConnectError::Request(_))));
+    }
+
+    #[tokio::test]
+    async fn connecting_to_an_invalid_host_does_not_panic() {
+        let result = ws_connect(&args(&["--server_ip", "not a host"]), 8443, Algorithm::HS256, "/messages").await;
+
+        assert!(matches!(result, Err(ConnectError::Request(_))));
+    }
+
+    #[test]
+    fn cursor_is_threaded_across_pages() {
+        let mut request = build_paged_search_request(&args(&["--search_keywords", "convoy"])).unwrap();
+        assert_eq!(request.cursor, None);
+
+        // The first page hands back the cursor for the second.
+        assert!(advance_cursor(&mut request, Some(String::from("AoE1"))));
+        assert!(request.to_json().contains(r#""cursor":"AoE1""#));
+
+        // The second page hands back the cursor for the third.
+        assert!(advance_cursor(&mut request, Some(String::from("AoE2"))));
+        assert_eq!(request.cursor.as_deref(), Some("AoE2"));
+
+        // The last page hands back the cursor it was asked for.
+        assert!(!advance_cursor(&mut request, Some(String::from("AoE2"))));
+        assert_eq!(request.cursor.as_deref(), Some("AoE2"));
+    }
+
+    #[test]
+    fn a_page_without_a_cursor_is_the_last() {
+        let mut request = build_paged_search_request(&args(&[])).unwrap();
+
+        assert!(!advance_cursor(&mut request, None));
+        assert_eq!(request.cursor, None);
+    }
+
+    // Gives the domain and room a serialized request is addressed to.
+    fn domain_and_room(request: &str) -> (String, String) {
+        let request: serde_json::Value = serde_json::from_str(request).unwrap();
+
+        (String::from(request["domainId"].as_str().unwrap()), String::from(request["roomName"].as_str().unwrap()))
+    }
+
+    #[test]
+    fn requests_carry_the_domain_and_room() {
+        let args = args(&["--domain", "chatsurferxmpplab", "--room", "convoy-watch"]);
+        let expected = (String::from("chatsurferxmpplab"), String::from("convoy-watch"));
+
+        assert_eq!(domain_and_room(&build_users_request(&args.domain(), args.room())), expected);
+        assert_eq!(domain_and_room(&build_messages_request(&args.domain(), args.room())), expected);
+        assert_eq!(domain_and_room(&build_search_messages_request(&args.domain(), args.room(), None, false, None)), expected);
+        assert_eq!(domain_and_room(&build_empty_search_request(&args.domain(), args.room())), expected);
+        assert_eq!(domain_and_room(&build_new_message_request_with_text(&args.domain(), args.room(), "Hello", None)), expected);
+    }
+
+    #[test]
+    fn network_picks_the_domain_unless_one_is_given() {
+        let args_with_network = args(&["--network", "sipr"]);
+        let args_with_both = args(&["--network", "sipr", "--domain", "chatsurferxmpplab"]);
+
+        assert_eq!(domain_and_room(&build_users_request(&args_with_network.domain(), args_with_network.room())).0, "chatsurferxmppsipr");
+        assert_eq!(domain_and_room(&build_users_request(&args_with_both.domain(), args_with_both.room())).0, "chatsurferxmpplab");
+    }
+
+    #[test]
+    fn header_mode_puts_the_token_in_the_authorization_header() {
+        let request = build_upgrade_request(&args(&["--auth_mode", "header"]), 7878, "token", "/users").unwrap();
+
+        assert_eq!(request.headers()["Authorization"], "Bearer token");
+        assert_eq!(request.headers().get("Cookie"), None);
+        assert_eq!(request.uri().query(), None);
+    }
+
+    #[test]
+    fn query_mode_puts_the_token_in_the_url() {
+        let request = build_upgrade_request(&args(&["--auth_mode", "query"]), 7878, "token", "/users").unwrap();
+
+        assert_eq!(request.uri().query(), Some("access_token=token"));
+        assert_eq!(request.headers().get("Authorization"), None);
+        assert_eq!(request.headers().get("Cookie"), None);
+
+        // A path with a query of its own keeps it.
+        let request = build_upgrade_request(&args(&["--auth_mode", "query"]), 7878, "token", "/users?limit=5").unwrap();
+
+        assert_eq!(request.uri().query(), Some("limit=5&access_token=token"));
+    }
+
+    #[test]
+    fn cookie_mode_puts_the_token_in_a_cookie() {
+        let request = build_upgrade_request(&args(&["--auth_mode", "cookie"]), 7878, "token", "/users").unwrap();
+
+        assert_eq!(request.headers()["Cookie"], "access_token=token");
+        assert_eq!(request.headers().get("Authorization"), None);
+        assert_eq!(request.uri().query(), None);
+    }
+
+    #[tokio::test]
+    async fn pipelined_responses_are_matched_by_correlation_id() {
+        let mut pending: HashMap<String, usize> = HashMap::from([
+            (String::from("first"), 0),
+            (String::from("second"), 1),
+        ]);
+        let mut tally = PipelineTally::default();
+        let mut read = futures_util::stream::iter([
+            Ok(Message::Text(String::from(r#"{ "correlationId": "second" }"#))),
+            Ok(Message::Text(String::from(r#"{ "correlationId": "unknown" }"#))),
+            Ok(Message::Text(String::from(r#"{ "correlationId": "first" }"#))),
+        ]);
+
+        for _ in 0..3 {
+            assert!(receive_pipelined(&mut read, &mut pending, &mut tally).await);
+        }
+        assert!(!receive_pipelined(&mut read, &mut pending, &mut tally).await);
+
+        assert!(pending.is_empty());
+        assert_eq!((tally.matched, tally.reordered, tally.unmatched), (2, 2, 1));
+    }
+}
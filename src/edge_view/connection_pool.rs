@@ -0,0 +1,102 @@
+use crate::cli::Args;
+use crate::edge_view::client::{
+    ClientSocket,
+    ConnectError,
+    debug,
+    error,
+    read_response,
+    ws_close,
+    ws_connect,
+};
+use futures_util::SinkExt;
+use jsonwebtoken::Algorithm;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+//==============================================================================
+// struct ConnectionPool
+//==============================================================================
+
+/// The ConnectionPool structure holds the connections left open between
+/// tests when --reuse_connection is given.  A connection can only carry
+/// requests for the endpoint and JWT it was opened with, so they are kept
+/// apart by both.
+pub struct ConnectionPool {
+    idle:   Mutex<HashMap<(String, Algorithm), Vec<ClientSocket>>>,
+}
+
+impl ConnectionPool {
+    pub fn new() -> ConnectionPool {
+        ConnectionPool { idle: Mutex::new(HashMap::new()) }
+    }
+
+    /*
+     * This method sends a request over an idle connection to the endpoint,
+     * opening one if there is none, and reads one reply.  The connection
+     * goes back into the pool only if the reply arrived, so that a late
+     * reply can't be mistaken for the answer to the next request, and
+     * wasn't the server closing it.
+     */
+    pub async fn send(
+        &self,
+        args:           &Args,
+        server_port:    u16,
+        jwt_alg:        Algorithm,
+        path:           &str,
+        message:        String,
+    ) -> Result<Option<Message>, ConnectError> {
+        let key = (String::from(path), jwt_alg);
+        let idle_socket = self.idle
+            .lock()
+            .unwrap()
+            .get_mut(&key)
+            .and_then(|sockets| sockets.pop());
+
+        let mut socket = match idle_socket {
+            Some(socket) => {
+                debug(format!("Reusing a connection to {}.", path));
+                socket
+            }
+            None => ws_connect(args, server_port, jwt_alg, path).await?,
+        };
+
+        if let Err(e) = socket.send(Message::Text(message)).await {
+            error(format!("Could not send the request: {}", e));
+            return Ok(None);
+        }
+
+        socket.get_ref().stats().record_request();
+
+        let response = read_response(args, &mut socket, path).await;
+
+        if response.as_ref().is_some_and(|response| !response.is_close()) {
+            self.idle
+                .lock()
+                .unwrap()
+                .entry(key)
+                .or_default()
+                .push(socket);
+        }
+
+        Ok(response)
+    } // end send
+
+    /*
+     * This method closes every connection left in the pool.
+     */
+    pub async fn close_all(&self, args: &Args) {
+        let idle: Vec<ClientSocket> = self.idle
+            .lock()
+            .unwrap()
+            .drain()
+            .flat_map(|(_, sockets)| sockets)
+            .collect();
+
+        for socket in idle {
+            ws_close(args, socket).await;
+        }
+    } // end close_all
+} // end ConnectionPool
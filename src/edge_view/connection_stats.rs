@@ -0,0 +1,182 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{ AtomicU64, AtomicUsize, Ordering },
+    },
+    task::{ Context, Poll },
+    time::{ Duration, Instant },
+};
+use tokio::io::{ AsyncRead, AsyncWrite, ReadBuf };
+use tracing::{event, Level};
+
+// With --connection_stats, every connection we open registers its statistics
+// here so that they can be summarized at the end of the run.
+static CONNECTIONS: Mutex<Vec<Arc<ConnectionStats>>> = Mutex::new(Vec::new());
+static NEXT_CONNECTION_ID: AtomicUsize = AtomicUsize::new(1);
+
+//==============================================================================
+// struct ConnectionStats
+//==============================================================================
+
+/// The ConnectionStats structure tallies the traffic carried by a single
+/// WebSocket connection over its lifetime.
+pub struct ConnectionStats {
+    pub id:             usize,
+    pub path:           String,
+    pub requests:       AtomicU64,
+    pub bytes_sent:     AtomicU64,
+    pub bytes_received: AtomicU64,
+    opened_at:          Instant,
+    lifetime:           Mutex<Option<Duration>>,
+}
+
+impl ConnectionStats {
+    /*
+     * This method creates the statistics for a newly opened connection
+     * without adding them to the registry, so that they are freed along
+     * with the connection.
+     */
+    pub fn new(path: &str) -> Arc<ConnectionStats> {
+        Arc::new(ConnectionStats {
+            id:             NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            path:           String::from(path),
+            requests:       AtomicU64::new(0),
+            bytes_sent:     AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            opened_at:      Instant::now(),
+            lifetime:       Mutex::new(None),
+        })
+    }
+
+    /*
+     * This method creates the statistics for a newly opened connection and
+     * adds them to the registry.
+     */
+    pub fn register(path: &str) -> Arc<ConnectionStats> {
+        let stats = ConnectionStats::new(path);
+
+        CONNECTIONS.lock().unwrap().push(stats.clone());
+
+        stats
+    }
+
+    /*
+     * This method counts one more request sent over the connection.
+     */
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /*
+     * This method returns how long the connection was open, or how long it
+     * has been open so far if it is still live.
+     */
+    pub fn lifetime(&self) -> Duration {
+        match *self.lifetime.lock().unwrap() {
+            Some(lifetime) => lifetime,
+            None => self.opened_at.elapsed(),
+        }
+    }
+
+    fn closed(&self) {
+        let mut lifetime = self.lifetime.lock().unwrap();
+
+        if lifetime.is_none() {
+            *lifetime = Some(self.opened_at.elapsed());
+        }
+    }
+} // end ConnectionStats
+
+/*
+ * This function logs the statistics of every connection opened during the
+ * run.
+ */
+pub fn log_connection_stats() {
+    let connections = CONNECTIONS.lock().unwrap();
+
+    event!(Level::INFO, "Connections opened: {}", connections.len());
+
+    for stats in connections.iter() {
+        event!(Level::INFO,
+            "Connection {} ({}): {} requests, {} bytes sent, {} bytes received, open for {} ms",
+            stats.id,
+            stats.path,
+            stats.requests.load(Ordering::Relaxed),
+            stats.bytes_sent.load(Ordering::Relaxed),
+            stats.bytes_received.load(Ordering::Relaxed),
+            stats.lifetime().as_millis()
+        );
+    }
+} // end log_connection_stats
+
+//==============================================================================
+// struct CountingStream
+//==============================================================================
+
+/// The CountingStream structure wraps the stream underneath a WebSocket
+/// connection and tallies every byte read from or written to it.
+pub struct CountingStream<S> {
+    inner:  S,
+    stats:  Arc<ConnectionStats>,
+}
+
+impl<S> CountingStream<S> {
+    pub fn new(inner: S, stats: Arc<ConnectionStats>) -> CountingStream<S> {
+        CountingStream { inner, stats }
+    }
+
+    pub fn stats(&self) -> Arc<ConnectionStats> {
+        self.stats.clone()
+    }
+}
+
+impl<S> Drop for CountingStream<S> {
+    fn drop(&mut self) {
+        self.stats.closed();
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CountingStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled_before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = result {
+            let bytes_read = (buf.filled().len() - filled_before) as u64;
+            self.stats.bytes_received.fetch_add(bytes_read, Ordering::Relaxed);
+        }
+
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for CountingStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(bytes_written)) = result {
+            self.stats.bytes_sent.fetch_add(bytes_written as u64, Ordering::Relaxed);
+        }
+
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
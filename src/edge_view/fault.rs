@@ -0,0 +1,101 @@
+use serde::Serialize;
+use std::time::Duration;
+use tokio::io::{ AsyncWrite, AsyncWriteExt };
+use tokio_tungstenite::tungstenite::protocol::frame::{
+    Frame,
+    coding::{ Data, OpCode },
+};
+
+//==============================================================================
+// enum Fault
+//==============================================================================
+
+/// The Fault enumeration lists the client-side faults that can be injected
+/// with --fault to see how the server copes with a misbehaving client.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Fault {
+    // Wait this long before sending each request.
+    SlowSend(Duration),
+
+    // Wait this long before reading each response.
+    SlowRead(Duration),
+
+    // Drop the connection right after sending the request, without reading
+    // the response or sending a closing frame.
+    DropAfterSend,
+
+    // Write only the first half of the request frame, then drop the
+    // connection.
+    HalfFrame,
+}
+
+/*
+ * This function parses a --fault argument, e.g. "slow_send=500ms" or
+ * "drop_after_send".
+ */
+pub fn parse_fault(fault: &str) -> Result<Fault, String> {
+    match fault.split_once('=') {
+        Some(("slow_send", delay)) => Ok(Fault::SlowSend(parse_duration(delay)?)),
+        Some(("slow_read", delay)) => Ok(Fault::SlowRead(parse_duration(delay)?)),
+        None if fault == "drop_after_send" => Ok(Fault::DropAfterSend),
+        None if fault == "half_frame" => Ok(Fault::HalfFrame),
+        _ => Err(format!(
+            "Unknown fault {}; expected slow_send=<delay>, slow_read=<delay>, drop_after_send, or half_frame",
+            fault)),
+    }
+} // end parse_fault
+
+/*
+ * This function parses a delay given in milliseconds ("500ms") or seconds
+ * ("2s").
+ */
+fn parse_duration(delay: &str) -> Result<Duration, String> {
+    let invalid = |_| format!("Invalid delay {}; expected e.g. 500ms or 2s", delay);
+
+    if let Some(millis) = delay.strip_suffix("ms") {
+        Ok(Duration::from_millis(millis.parse().map_err(invalid)?))
+    } else if let Some(secs) = delay.strip_suffix('s') {
+        Ok(Duration::from_secs(secs.parse().map_err(invalid)?))
+    } else {
+        Err(format!("Invalid delay {}; expected e.g. 500ms or 2s", delay))
+    }
+} // end parse_duration
+
+pub fn slow_send(faults: &[Fault]) -> Option<Duration> {
+    faults.iter().find_map(|fault| match fault {
+        Fault::SlowSend(delay) => Some(*delay),
+        _ => None,
+    })
+}
+
+pub fn slow_read(faults: &[Fault]) -> Option<Duration> {
+    faults.iter().find_map(|fault| match fault {
+        Fault::SlowRead(delay) => Some(*delay),
+        _ => None,
+    })
+}
+
+/*
+ * This function writes the first half of a masked text frame carrying the
+ * message straight to the underlying stream, bypassing the WebSocket layer.
+ */
+pub async fn send_half_frame<S>(stream: &mut S, message: &str) -> std::io::Result<()>
+where
+    S: AsyncWrite + Unpin
+{
+    let mut frame = Frame::message(
+        message.as_bytes().to_vec(),
+        OpCode::Data(Data::Text),
+        true);
+
+    // Clients must mask every frame they send.
+    frame.header_mut().mask = Some(rand::random());
+
+    let mut bytes: Vec<u8> = Vec::new();
+    frame
+        .format(&mut bytes)
+        .map_err(std::io::Error::other)?;
+
+    stream.write_all(&bytes[..bytes.len() / 2]).await?;
+    stream.flush().await
+} // end send_half_frame
@@ -0,0 +1,427 @@
+use crate::edge_view::client::{ debug, ConnectError };
+use bytes::Bytes;
+use h2::{ ext::Protocol, Ping, RecvStream, SendStream };
+use http::{
+    HeaderMap,
+    Method,
+    Request,
+    StatusCode,
+    Uri,
+    header::{ CONNECTION, HeaderName, HOST, SEC_WEBSOCKET_KEY, UPGRADE },
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{ Context, Poll, ready },
+};
+use tokio::{
+    io::{ AsyncRead, AsyncWrite, ReadBuf },
+    net::TcpStream,
+};
+use tokio_tungstenite::MaybeTlsStream;
+
+// The protocol name offered with ALPN, and chosen by servers that speak
+// HTTP/2 over TLS.
+pub const ALPN_HTTP2: &[u8] = b"h2";
+
+// The headers of the HTTP/1.1 upgrade request that have no place in an
+// extended CONNECT.  HTTP/2 carries the host in the :authority
+// pseudo-header and forbids connection-specific headers, and RFC 8441 does
+// without the key.
+const HTTP1_ONLY_HEADERS: [HeaderName; 4] = [CONNECTION, HOST, SEC_WEBSOCKET_KEY, UPGRADE];
+
+//==============================================================================
+// enum ClientStream
+//==============================================================================
+
+/// The ClientStream enumeration is the stream underneath a WebSocket
+/// connection: the TCP or TLS connection itself after an HTTP/1.1 Upgrade,
+/// or one stream of an HTTP/2 connection after an extended CONNECT.
+// Nearly every connection is HTTP/1.1, so its stream is kept inline
+// rather than boxed to save the space an Http2Stream leaves unused.
+#[allow(clippy::large_enum_variant)]
+pub enum ClientStream {
+    Http1(MaybeTlsStream<TcpStream>),
+    Http2(Http2Stream),
+}
+
+impl AsyncRead for ClientStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Http1(stream) => Pin::new(stream).poll_read(cx, buf),
+            ClientStream::Http2(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ClientStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ClientStream::Http1(stream) => Pin::new(stream).poll_write(cx, buf),
+            ClientStream::Http2(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Http1(stream) => Pin::new(stream).poll_flush(cx),
+            ClientStream::Http2(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Http1(stream) => Pin::new(stream).poll_shutdown(cx),
+            ClientStream::Http2(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+//==============================================================================
+// struct Http2Stream
+//==============================================================================
+
+/// The Http2Stream structure reads and writes the data of one HTTP/2
+/// stream as if it were a connection of its own, so that a WebSocket can be
+/// run over it.
+pub struct Http2Stream {
+    send:       SendStream<Bytes>,
+    recv:       RecvStream,
+
+    // Data received from the server that hasn't been read yet.
+    pending:    Bytes,
+}
+
+impl Http2Stream {
+    fn new(send: SendStream<Bytes>, recv: RecvStream) -> Http2Stream {
+        Http2Stream {
+            send,
+            recv,
+            pending:    Bytes::new(),
+        }
+    }
+}
+
+impl AsyncRead for Http2Stream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        // An empty DATA frame must not be mistaken for the end of the stream.
+        while self.pending.is_empty() {
+            match ready!(self.recv.poll_data(cx)) {
+                Some(Ok(data)) => {
+                    // Let the server send more as soon as this is taken.
+                    let _ = self.recv.flow_control().release_capacity(data.len());
+                    self.pending = data;
+                }
+                Some(Err(e)) => return Poll::Ready(Err(io::Error::other(e))),
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let count = self.pending.len().min(buf.remaining());
+        let data = self.pending.split_to(count);
+        buf.put_slice(&data);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Http2Stream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        // Only as much as the server's flow control window allows can be
+        // sent at once.
+        self.send.reserve_capacity(buf.len());
+
+        match ready!(self.send.poll_capacity(cx)) {
+            Some(Ok(capacity)) => {
+                let count = capacity.min(buf.len());
+
+                self.send
+                    .send_data(Bytes::copy_from_slice(&buf[..count]), false)
+                    .map_err(io::Error::other)?;
+
+                Poll::Ready(Ok(count))
+            }
+            Some(Err(e)) => Poll::Ready(Err(io::Error::other(e))),
+            None => Poll::Ready(Err(io::Error::from(io::ErrorKind::BrokenPipe))),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // The stream may already have been reset by the server, in which
+        // case there is nothing left to end.
+        let _ = self.send.send_data(Bytes::new(), true);
+
+        Poll::Ready(Ok(()))
+    }
+} // end Http2Stream
+
+//==============================================================================
+// enum Http2Connect
+//==============================================================================
+
+/// The Http2Connect enumeration describes how an attempt to open a WebSocket
+/// with an extended CONNECT turned out, short of an error.
+pub enum Http2Connect {
+    // The server answered the CONNECT with this status and these headers.
+    // Only a 2xx status opens the WebSocket.
+    Answered {
+        stream:     Http2Stream,
+        status:     StatusCode,
+        headers:    HeaderMap,
+    },
+
+    // The server can't take a WebSocket over HTTP/2, for the given reason,
+    // so the connection should be made with an HTTP/1.1 Upgrade instead.
+    Unsupported(String),
+}
+
+/*
+ * This function tells whether HTTP/2 can be tried on a connection.  It is
+ * spoken with prior knowledge on a plain connection, but over TLS only when
+ * the server chose it with ALPN.
+ */
+pub fn offers_http2(stream: &MaybeTlsStream<TcpStream>) -> bool {
+    match stream {
+        MaybeTlsStream::NativeTls(stream) => {
+            matches!(stream.get_ref().negotiated_alpn(), Ok(Some(protocol)) if protocol == ALPN_HTTP2)
+        }
+        _ => true,
+    }
+} // end offers_http2
+
+/*
+ * This function turns the HTTP/1.1 upgrade request into the extended
+ * CONNECT that asks for a WebSocket over HTTP/2, as RFC 8441 describes.
+ * The JWT and any other headers carry over unchanged.
+ */
+fn build_connect_request(upgrade_request: &Request<()>) -> Result<Request<()>, ConnectError> {
+    let upgrade_uri = upgrade_request.uri();
+
+    let uri = Uri::builder()
+        .scheme(if upgrade_uri.scheme_str() == Some("wss") { "https" } else { "http" })
+        .authority(upgrade_uri.authority().map(|authority| authority.as_str()).unwrap_or_default())
+        .path_and_query(upgrade_uri.path_and_query().map(|path| path.as_str()).unwrap_or("/"))
+        .build()
+        .map_err(|e| ConnectError::Request(format!("Could not build the CONNECT request for {}: {}", upgrade_uri, e)))?;
+
+    let mut request = Request::builder()
+        .method(Method::CONNECT)
+        .uri(uri)
+        .extension(Protocol::from_static("websocket"))
+        .body(())
+        .map_err(|e| ConnectError::Request(format!("Could not build the CONNECT request for {}: {}", upgrade_uri, e)))?;
+
+    for (name, value) in upgrade_request.headers() {
+        if !HTTP1_ONLY_HEADERS.contains(name) {
+            request.headers_mut().append(name, value.clone());
+        }
+    }
+
+    Ok(request)
+} // end build_connect_request
+
+/*
+ * This function starts HTTP/2 on a connection and asks the server, with an
+ * extended CONNECT, for a stream to run a WebSocket over.  A server that
+ * doesn't speak HTTP/2, or doesn't allow extended CONNECT, is reported as
+ * Unsupported rather than as an error.
+ */
+pub async fn extended_connect<S>(io: S, upgrade_request: &Request<()>) -> Result<Http2Connect, ConnectError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static
+{
+    let request = build_connect_request(upgrade_request)?;
+
+    let (send_request, mut connection) = match h2::client::handshake(io).await {
+        Ok(handshake) => handshake,
+        Err(e) => return Ok(Http2Connect::Unsupported(format!("the HTTP/2 handshake failed: {}", e))),
+    };
+
+    // The connection is only ever asked for its PingPong here.
+    let mut ping_pong = connection.ping_pong();
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug(format!("The HTTP/2 connection ended with an error: {}", e));
+        }
+    });
+
+    // The server's SETTINGS come before its answer to our PING, so once the
+    // PING is answered we know whether it allows extended CONNECT.
+    if let Some(ping_pong) = &mut ping_pong {
+        if let Err(e) = ping_pong.ping(Ping::opaque()).await {
+            return Ok(Http2Connect::Unsupported(format!("the server did not answer over HTTP/2: {}", e)));
+        }
+    }
+
+    let mut send_request = match send_request.ready().await {
+        Ok(send_request) => send_request,
+        Err(e) => return Ok(Http2Connect::Unsupported(format!("the server did not answer over HTTP/2: {}", e))),
+    };
+
+    if !send_request.is_extended_connect_protocol_enabled() {
+        return Ok(Http2Connect::Unsupported(String::from("the server does not allow extended CONNECT")));
+    }
+
+    let refused = |e: h2::Error| ConnectError::Handshake {
+        status:     None,
+        headers:    HeaderMap::new(),
+        reason:     e.to_string(),
+    };
+
+    let (response, send) = send_request
+        .send_request(request, false)
+        .map_err(refused)?;
+    let (parts, recv) = response
+        .await
+        .map_err(refused)?
+        .into_parts();
+
+    Ok(Http2Connect::Answered {
+        stream:     Http2Stream::new(send, recv),
+        status:     parts.status,
+        headers:    parts.headers,
+    })
+} // end extended_connect
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use crate::edge_view::client::{ ws_close, ws_connect };
+    use clap::Parser;
+    use futures_util::{ SinkExt, StreamExt };
+    use jsonwebtoken::Algorithm;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{
+        WebSocketStream,
+        tungstenite::{ client::IntoClientRequest, protocol::{ Message, Role } },
+    };
+
+    // Parses the arguments for connecting over HTTP/2 to a local server.
+    fn http2_args() -> Args {
+        Args::try_parse_from(["WebSocket-TestClient", "--server_ip", "127.0.0.1", "--http2"]).unwrap()
+    }
+
+    // Serves one HTTP/2 connection that allows extended CONNECT, and
+    // echoes the first message sent over the first WebSocket opened on it.
+    // It gives back the path and protocol the CONNECT asked for.  It speaks
+    // nothing but HTTP/2, so an echo means the WebSocket ran over HTTP/2.
+    async fn serve_http2_echo(listener: TcpListener) -> (String, Option<String>) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut connection = h2::server::Builder::new()
+            .enable_connect_protocol()
+            .handshake::<_, Bytes>(stream)
+            .await
+            .unwrap();
+
+        let (request, mut respond) = connection.accept().await.unwrap().unwrap();
+        tokio::spawn(async move { while connection.accept().await.is_some() {} });
+
+        let path = String::from(request.uri().path());
+        let protocol = request.extensions().get::<Protocol>().map(|protocol| String::from(protocol.as_str()));
+
+        let send = respond.send_response(http::Response::new(()), false).unwrap();
+        let stream = Http2Stream::new(send, request.into_body());
+        let mut socket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+
+        if let Some(Ok(message)) = socket.next().await {
+            socket.send(message).await.unwrap();
+        }
+
+        // Dropping the stream would reset it, so wait for the client to
+        // finish with it first.
+        while let Some(Ok(_)) = socket.next().await {}
+
+        (path, protocol)
+    }
+
+    #[test]
+    fn connect_request_is_an_extended_connect() {
+        let mut upgrade_request = "wss://edge-view.example.com:8443/users?limit=5".into_client_request().unwrap();
+        upgrade_request.headers_mut().insert("Authorization", "Bearer token".parse().unwrap());
+
+        let request = build_connect_request(&upgrade_request).unwrap();
+
+        assert_eq!(request.method(), Method::CONNECT);
+        assert_eq!(request.uri().to_string(), "https://edge-view.example.com:8443/users?limit=5");
+        assert_eq!(request.extensions().get::<Protocol>(), Some(&Protocol::from_static("websocket")));
+        assert_eq!(request.headers()["Authorization"], "Bearer token");
+        assert_eq!(request.headers()["Sec-WebSocket-Version"], "13");
+
+        for name in HTTP1_ONLY_HEADERS {
+            assert!(!request.headers().contains_key(name));
+        }
+    }
+
+    #[tokio::test]
+    async fn websocket_runs_over_an_http2_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(serve_http2_echo(listener));
+
+        let args = http2_args();
+        let mut socket = ws_connect(&args, port, Algorithm::HS256, "/users").await.unwrap();
+
+        socket.send(Message::text("over HTTP/2")).await.unwrap();
+        assert_eq!(socket.next().await.unwrap().unwrap(), Message::text("over HTTP/2"));
+
+        ws_close(&args, socket).await;
+
+        assert_eq!(server.await.unwrap(), (String::from("/users"), Some(String::from("websocket"))));
+    }
+
+    #[tokio::test]
+    async fn server_without_http2_gets_an_http1_upgrade() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // The first connection starts with the HTTP/2 preface, which an
+        // HTTP/1.1 server refuses.  The second is the fallback.
+        let server = tokio::spawn(async move {
+            let mut connections = 0;
+
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                connections += 1;
+
+                if let Ok(mut socket) = tokio_tungstenite::accept_async(stream).await {
+                    let message = socket.next().await.unwrap().unwrap();
+                    socket.send(message).await.unwrap();
+                    return connections;
+                }
+            }
+        });
+
+        let mut socket = ws_connect(&http2_args(), port, Algorithm::HS256, "/users").await.unwrap();
+
+        socket.send(Message::text("over HTTP/1.1")).await.unwrap();
+        assert_eq!(socket.next().await.unwrap().unwrap(), Message::text("over HTTP/1.1"));
+
+        assert_eq!(server.await.unwrap(), 2);
+    }
+}
@@ -1,2 +1,9 @@
-
-pub mod client;
\ No newline at end of file
+pub mod classification_order;
+pub mod client;
+pub mod connection_pool;
+pub mod connection_stats;
+pub mod fault;
+pub mod http2;
+pub mod proxy;
+pub mod rate_limit;
+pub mod shutdown;
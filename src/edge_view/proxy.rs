@@ -0,0 +1,124 @@
+use crate::edge_view::client::debug;
+use base64::{ Engine, engine::general_purpose::STANDARD };
+use tokio::{
+    io::{ AsyncReadExt, AsyncWriteExt },
+    net::TcpStream,
+};
+
+// The port used when --proxy doesn't give one, as for any http:// URL.
+const DEFAULT_PROXY_PORT: u16 = 80;
+
+// The most of the proxy's answer to CONNECT we read before giving up on it.
+const MAX_CONNECT_RESPONSE_BYTES: usize = 8192;
+
+//==============================================================================
+// struct Proxy
+//==============================================================================
+
+/// The Proxy structure describes the HTTP proxy given with --proxy, through
+/// which ws_connect tunnels its connections with CONNECT.
+#[derive(Debug, PartialEq)]
+pub struct Proxy {
+    pub host:           String,
+    pub port:           u16,
+
+    // The "user:password" given in the URL, if any.
+    pub credentials:    Option<String>,
+}
+
+impl Proxy {
+    /*
+     * This method parses a proxy URL of the form
+     * http://[user:password@]host[:port].  The credentials are used as
+     * written, without percent-decoding.
+     */
+    pub fn parse(url: &str) -> Result<Proxy, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or(format!("The proxy {} is not an http:// URL.", url))?
+            .trim_end_matches('/');
+
+        let (credentials, address) = match rest.rsplit_once('@') {
+            Some((credentials, address)) => (Some(String::from(credentials)), address),
+            None => (None, rest),
+        };
+
+        // A bracketed IPv6 address has colons of its own, so only a colon
+        // after the closing bracket starts the port.
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) if !port.ends_with(']') => {
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| format!("The proxy port {} is not a port number.", port))?;
+
+                (host, port)
+            }
+            _ => (address, DEFAULT_PROXY_PORT),
+        };
+
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        if host.is_empty() {
+            return Err(format!("The proxy {} has no host.", url));
+        }
+
+        Ok(Proxy {
+            host:           String::from(host),
+            port,
+            credentials,
+        })
+    }
+
+    /*
+     * This method asks the proxy, over a connection already open to it, to
+     * tunnel to the target.  Anything but a 2xx answer is an error carrying
+     * the proxy's status line.
+     */
+    pub async fn connect_tunnel(
+        &self,
+        stream:         &mut TcpStream,
+        target:         &str,
+        credentials:    Option<&str>,
+    ) -> Result<(), String> {
+        let mut request = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", target, target);
+
+        if let Some(credentials) = credentials {
+            request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", STANDARD.encode(credentials)));
+        }
+
+        request.push_str("\r\n");
+
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| format!("Could not send CONNECT to the proxy: {}", e))?;
+
+        // Read the answer a byte at a time so that nothing past its end,
+        // which belongs to the tunnel, is consumed.
+        let mut response: Vec<u8> = Vec::new();
+
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_CONNECT_RESPONSE_BYTES {
+                return Err(format!("The proxy's answer to CONNECT is longer than {} bytes.", MAX_CONNECT_RESPONSE_BYTES));
+            }
+
+            let byte = stream
+                .read_u8()
+                .await
+                .map_err(|e| format!("The proxy closed the connection before answering CONNECT: {}", e))?;
+
+            response.push(byte);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => {
+                debug(format!("The proxy opened a tunnel to {}: {}", target, status_line));
+                Ok(())
+            }
+            _ => Err(format!("The proxy refused to tunnel to {}: {}", target, status_line)),
+        }
+    } // end connect_tunnel
+} // end Proxy
@@ -0,0 +1,128 @@
+use crate::edge_view::client::debug;
+use std::time::{ Duration, Instant };
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+// The most requests ChatSurfer accepts from one client in a minute.
+pub const MAX_REQUESTS_PER_MINUTE: u32 = 60;
+
+// How long to wait before retrying a request that was refused with a 429.
+// The wait doubles with each further retry.
+pub const RATE_LIMIT_INITIAL_BACKOFF_MS: u64 = 1000;
+
+// The error code the server responds with when we send too many requests.
+const TOO_MANY_REQUESTS: i64 = 429;
+
+//==============================================================================
+// struct RateLimiter
+//==============================================================================
+
+/// The RateLimiter structure is a token bucket that keeps a client under a
+/// number of requests per minute.  The bucket starts full, so a short burst
+/// goes out at once, and then refills at an even pace.
+pub struct RateLimiter {
+    capacity:       f64,
+    tokens:         f64,
+    refill_per_sec: f64,
+    refilled_at:    Instant,
+}
+
+impl RateLimiter {
+    pub fn per_minute(requests: u32) -> RateLimiter {
+        let capacity = requests.max(1) as f64;
+
+        RateLimiter {
+            capacity,
+            tokens:         capacity,
+            refill_per_sec: capacity / 60.0,
+            refilled_at:    Instant::now(),
+        }
+    }
+
+    /*
+     * This method waits until a request may be sent, and takes a token for
+     * it.
+     */
+    pub async fn acquire(&mut self) {
+        self.refill();
+
+        if self.tokens < 1.0 {
+            let wait = Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec);
+
+            debug(format!("Rate limiting: waiting {} ms before the next request.", wait.as_millis()));
+            tokio::time::sleep(wait).await;
+            self.refill();
+        }
+
+        self.tokens -= 1.0;
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.refilled_at = now;
+    }
+} // end RateLimiter
+
+/*
+ * This function checks whether a response is the server refusing a request
+ * because we sent too many.
+ */
+pub fn is_rate_limited(payload: &Message) -> bool {
+    payload
+        .to_text()
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(text).ok())
+        .and_then(|value| value["code"].as_i64())
+        == Some(TOO_MANY_REQUESTS)
+} // end is_rate_limited
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn burst_up_to_capacity_is_not_delayed() {
+        let mut limiter = RateLimiter::per_minute(600);
+        let started = Instant::now();
+
+        for _ in 0..600 {
+            limiter.acquire().await;
+        }
+
+        assert!(started.elapsed() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn request_past_capacity_waits_for_a_refill() {
+        // 600 a minute refills a token every 100 ms.
+        let mut limiter = RateLimiter::per_minute(600);
+
+        for _ in 0..600 {
+            limiter.acquire().await;
+        }
+
+        let started = Instant::now();
+        limiter.acquire().await;
+
+        assert!(started.elapsed() >= Duration::from_millis(90));
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn zero_per_minute_still_allows_one_request() {
+        let limiter = RateLimiter::per_minute(0);
+
+        assert_eq!(limiter.capacity, 1.0);
+        assert_eq!(limiter.tokens, 1.0);
+    }
+
+    #[test]
+    fn only_a_429_is_rate_limited() {
+        assert!(is_rate_limited(&Message::text(r#"{"classification":"UNCLASSIFIED","code":429,"message":"Too Many Requests"}"#)));
+        assert!(!is_rate_limited(&Message::text(r#"{"classification":"UNCLASSIFIED","code":400,"message":"Bad Request"}"#)));
+        assert!(!is_rate_limited(&Message::text("429")));
+        assert!(!is_rate_limited(&Message::binary(vec![0xff, 0xfe])));
+    }
+}
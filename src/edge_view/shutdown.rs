@@ -0,0 +1,76 @@
+use crate::test_case::INTERRUPTED_EXIT_CODE;
+use std::{
+    borrow::Cow,
+    sync::LazyLock,
+};
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::protocol::{
+    CloseFrame,
+    frame::coding::CloseCode,
+};
+use tracing::{event, Level};
+
+// How long the open connections get to close after Ctrl-C before they are
+// cut off.
+pub const SHUTDOWN_GRACE_PERIOD_MS: u64 = 2000;
+
+// Set when Ctrl-C is pressed.  Whatever holds a long-lived connection
+// watches this and closes the connection properly before the client exits.
+static SHUTDOWN: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::Sender::new(false));
+
+/*
+ * This function installs the Ctrl-C handler.  The first Ctrl-C asks every
+ * open connection to close; a second one exits at once.
+ */
+pub fn handle_ctrl_c() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+
+        event!(Level::INFO, "Closing the open connections.  Press Ctrl-C again to exit immediately.");
+        request();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    });
+} // end handle_ctrl_c
+
+/*
+ * This function asks every open connection to close, just as Ctrl-C does.
+ * The test runner uses it to wind down a run it has given up on.
+ */
+pub fn request() {
+    SHUTDOWN.send_replace(true);
+} // end request
+
+/*
+ * This function tells whether Ctrl-C has been pressed, or a shutdown was
+ * otherwise requested.
+ */
+pub fn is_requested() -> bool {
+    *SHUTDOWN.borrow()
+} // end is_requested
+
+/*
+ * This function completes once Ctrl-C has been pressed, so that a loop
+ * holding a connection can select on it.
+ */
+pub async fn requested() {
+    let mut receiver = SHUTDOWN.subscribe();
+
+    // The sender is static, so it is never dropped.
+    let _ = receiver.wait_for(|requested| *requested).await;
+} // end requested
+
+/*
+ * This function builds the closing frame sent on the connections that are
+ * still open when Ctrl-C is pressed.
+ */
+pub fn close_frame() -> CloseFrame<'static> {
+    CloseFrame {
+        code:   CloseCode::Normal,
+        reason: Cow::Borrowed("Client shutting down"),
+    }
+} // end close_frame
@@ -0,0 +1,125 @@
+use std::collections::{ BTreeMap, HashSet };
+use tracing::{event, Level};
+
+// The optional fields of the responses we model, by endpoint.  These are
+// always listed in the report, even when no response ever populated them.
+const OPTIONAL_FIELDS: [(&str, &str); 3] = [
+    ("/messages",   "/messages[]/geoTags"),
+    ("/search",     "/messages[]/geoTags"),
+    ("/search",     "/total"),
+];
+
+//==============================================================================
+// struct FieldCoverage
+//==============================================================================
+
+/// The FieldCoverage structure tallies, for each endpoint, how many of the
+/// responses populated each field, so that we can see what the server
+/// actually sends versus what the schema allows.
+pub struct FieldCoverage {
+    // The number of responses seen from each endpoint.
+    responses:  BTreeMap<String, usize>,
+
+    // The number of responses from each endpoint that populated each field,
+    // keyed by endpoint and then by field path.
+    populated:  BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl FieldCoverage {
+    pub fn new() -> FieldCoverage {
+        let mut populated: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+
+        for (endpoint, path) in OPTIONAL_FIELDS {
+            populated
+                .entry(String::from(endpoint))
+                .or_default()
+                .insert(String::from(path), 0);
+        }
+
+        FieldCoverage {
+            responses: BTreeMap::new(),
+            populated,
+        }
+    }
+
+    /*
+     * This method records which fields a response from the given endpoint
+     * populated.  A field counts as populated when it is present and not
+     * null.  Array indices are folded into "[]" so that every element of an
+     * array contributes to the same field.
+     */
+    pub fn record(&mut self, endpoint: &str, response: &str) {
+        let value: serde_json::Value = match serde_json::from_str(response) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+
+        let mut paths: HashSet<String> = HashSet::new();
+        collect_populated_paths(&value, "", &mut paths);
+
+        *self.responses.entry(String::from(endpoint)).or_default() += 1;
+
+        let fields = self.populated.entry(String::from(endpoint)).or_default();
+
+        for path in paths {
+            *fields.entry(path).or_default() += 1;
+        }
+    }
+
+    /*
+     * This method logs a table of every field seen or expected for each
+     * endpoint, and how many of that endpoint's responses populated it.
+     */
+    pub fn log_report(&self) {
+        event!(Level::INFO, "Field coverage:");
+
+        for (endpoint, fields) in &self.populated {
+            let responses = self.responses.get(endpoint).copied().unwrap_or_default();
+
+            event!(Level::INFO, "  {} ({} responses)", endpoint, responses);
+
+            for (path, count) in fields {
+                let coverage = match *count {
+                    0 => "never",
+                    count if count == responses => "always",
+                    _ => "sometimes",
+                };
+
+                event!(Level::INFO, "    {:<40} {:>9} {}/{}", path, coverage, count, responses);
+            }
+        }
+    }
+} // end FieldCoverage
+
+/*
+ * This function adds the path of every non-null value within the given JSON
+ * value to the set.
+ */
+fn collect_populated_paths(value: &serde_json::Value, path: &str, paths: &mut HashSet<String>) {
+    match value {
+        serde_json::Value::Null => {}
+        serde_json::Value::Array(values) => {
+            if !path.is_empty() {
+                paths.insert(String::from(path));
+            }
+
+            let element_path = format!("{}[]", path);
+
+            for value in values {
+                collect_populated_paths(value, &element_path, paths);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            if !path.is_empty() {
+                paths.insert(String::from(path));
+            }
+
+            for (key, value) in fields {
+                collect_populated_paths(value, &format!("{}/{}", path, key), paths);
+            }
+        }
+        _ => {
+            paths.insert(String::from(path));
+        }
+    }
+} // end collect_populated_paths
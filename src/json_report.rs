@@ -0,0 +1,73 @@
+use crate::test_case::TestResult;
+use jsonwebtoken::Algorithm;
+use serde::Serialize;
+
+//==============================================================================
+// struct TestReport
+//==============================================================================
+
+/// The TestReport structure is the machine-readable summary of a run that
+/// --report_json writes, so that runs can be diffed against each other.
+#[derive(Serialize)]
+pub struct TestReport<'a> {
+    pub total:  usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub tests:  Vec<TestReportEntry<'a>>,
+}
+
+/// The TestReportEntry structure describes the outcome of a single test in
+/// a TestReport.
+#[derive(Serialize)]
+pub struct TestReportEntry<'a> {
+    pub test_name:      &'a str,
+    pub server_path:    &'a str,
+    pub jwt_header_alg: Algorithm,
+    pub passed:         bool,
+    pub duration_ms:    u64,
+
+    // Why the test failed, if it did.
+    pub error:          Option<&'a str>,
+
+    // The response as received, if there was one.
+    pub response:       Option<&'a str>,
+}
+
+impl<'a> TestReport<'a> {
+    pub fn new(results: &'a [TestResult], total_tests: usize) -> TestReport<'a> {
+        let tests: Vec<TestReportEntry> = results
+            .iter()
+            .map(|result| TestReportEntry {
+                test_name:      &result.name,
+                server_path:    &result.server_path,
+                jwt_header_alg: result.jwt_header_alg,
+                passed:         result.passed,
+                duration_ms:    result.latency.as_millis() as u64,
+                error:          result.failure.as_deref(),
+                response:       result.payload.as_deref(),
+            })
+            .collect();
+
+        let passed = tests.iter().filter(|test| test.passed).count();
+
+        TestReport {
+            total:  total_tests,
+            passed,
+            // Tests that never ran because the run was aborted count as
+            // failed.
+            failed: total_tests - passed,
+            tests,
+        }
+    }
+} // end TestReport
+
+/*
+ * This function writes the JSON report for a run to the given path.
+ */
+pub fn write_json_report(path: &str, results: &[TestResult], total_tests: usize) -> Result<(), String> {
+    let report = serde_json::to_string_pretty(&TestReport::new(results, total_tests))
+        .map_err(|e| format!("Could not serialize the JSON report: {}", e))?;
+
+    std::fs::write(path, report)
+        .map_err(|e| format!("Could not write the JSON report to {}: {}", path, e))
+} // end write_json_report
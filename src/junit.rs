@@ -0,0 +1,78 @@
+use crate::test_case::TestResult;
+
+// The name of the <testsuite> element, as it shows up in the CI test report.
+const TEST_SUITE_NAME: &str = "WebSocket-TestClient";
+
+/*
+ * This function escapes the characters that are not allowed to appear
+ * as-is in XML text or attribute values.
+ */
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&'  => escaped.push_str("&amp;"),
+            '<'  => escaped.push_str("&lt;"),
+            '>'  => escaped.push_str("&gt;"),
+            '"'  => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML 1.0 has no way to represent most control characters, even
+            // escaped, so they are dropped.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/*
+ * This function renders the results of a run as a JUnit XML <testsuite>,
+ * with one <testcase> per test.  Each failed test carries a <failure>
+ * element holding the reason it failed and the response it received.
+ */
+pub fn render_junit(results: &[TestResult], total_tests: usize) -> String {
+    let failures = results.iter().filter(|result| !result.passed).count();
+    let total_seconds: f64 = results
+        .iter()
+        .map(|result| result.latency.as_secs_f64())
+        .sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+    xml += &format!("<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        TEST_SUITE_NAME,
+        results.len(),
+        failures,
+        total_tests.saturating_sub(results.len()),
+        total_seconds);
+
+    for result in results {
+        xml += &format!("  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            xml_escape(&result.name),
+            TEST_SUITE_NAME,
+            result.latency.as_secs_f64());
+
+        if result.passed {
+            xml += "/>\n";
+            continue;
+        }
+
+        xml += ">\n";
+        xml += &format!("    <failure message=\"{}\">{}</failure>\n",
+            xml_escape(result.failure.as_deref().unwrap_or("Test failed.")),
+            xml_escape(result.payload.as_deref().unwrap_or_default()));
+        xml += "  </testcase>\n";
+    }
+
+    xml + "</testsuite>\n"
+} // end render_junit
+
+/*
+ * This function writes the JUnit XML report for a run to the given path.
+ */
+pub fn write_junit_report(path: &str, results: &[TestResult], total_tests: usize) -> Result<(), String> {
+    std::fs::write(path, render_junit(results, total_tests))
+        .map_err(|e| format!("Could not write the JUnit report to {}: {}", path, e))
+} // end write_junit_report
@@ -1,561 +1,518 @@
 
+mod bench_report;
 #[allow(non_snake_case)]
 mod chatsurfer;
 mod cli;
 use dotenv::dotenv;
 mod edge_view;
+mod field_coverage;
+mod json_report;
+mod junit;
 use futures_util::{ SinkExt, StreamExt };
-use jsonwebtoken::{
-    Algorithm,
-    encode,
-    EncodingKey,
-    Header,
-};
+use jsonwebtoken::Algorithm;
 mod messages;
-use messages::{
-    Account,
-    EdgeViewClaims,
-    GetMessagesRequest,
-    GetUsersRequest,
-    RealmAccess,
-    RealmManagement,
-    ResourceAccess,
-    SearchMessagesRequest,
-    SendNewMessageRequest,
+mod summary;
+mod test_case;
+use edge_view::rate_limit::{
+    is_rate_limited,
+    MAX_REQUESTS_PER_MINUTE,
+    RATE_LIMIT_INITIAL_BACKOFF_MS,
+    RateLimiter,
 };
 use serde_json;
 use std::time;
-use tokio::net::TcpStream;
-use tokio_tungstenite::{
-    client_async,
-    tungstenite::{
-        client::IntoClientRequest, http::HeaderValue, protocol::{CloseFrame, Message},
-        protocol::frame::coding::CloseCode,
-    },
-    WebSocketStream,
-};
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::protocol::Message;
 use tracing::{ event, Level };
-use tracing_subscriber::{ EnvFilter, fmt, prelude::* };
-use uuid::Uuid;
+use tracing_subscriber::{ EnvFilter, filter::LevelFilter, fmt, prelude::* };
+
+/*
+ * This function sends the same request several times, either over one
+ * shared connection or, with --repeat_new_connection, over a fresh
+ * connection per iteration.  The handshake and round trip latencies are
+ * reported separately so that the two modes can be compared.  When
+ * compare_responses is set, every response must also be structurally
+ * identical to the first one.
+ */
+async fn repeat_test(
+    args:               &cli::Args,
+    name:               &str,
+    path:               &str,
+    build_request:      impl Fn() -> String,
+    compare_responses:  bool,
+) -> bool {
+    let number_of_iterations: u32 = args.repeat_count;
+    let mut number_of_successes: u32 = 0;
+    let mut connections_opened: u32 = 0;
+    let mut handshake_time = time::Duration::ZERO;
+    let mut round_trip_time = time::Duration::ZERO;
+    let mut first_response: Option<serde_json::Value> = None;
+    let mut responses_differing: u32 = 0;
+
+    let mode = if args.repeat_new_connection {
+        "new connection per iteration"
+    } else {
+        "one connection"
+    };
 
+    event!(Level::INFO, "Beginning {} Repeat Test ({}).", name, mode);
 
-const TEST_DOMAIN: &str = "chatsurferxmppunclass";
-const TEST_ROOM: &str = "edge-view-test-room";
+    let mut client_socket: Option<edge_view::client::ClientSocket> = None;
+    let mut rate_limiter = RateLimiter::per_minute(MAX_REQUESTS_PER_MINUTE);
 
-fn get_users_message() -> String {
-    let get_users_request: GetUsersRequest = GetUsersRequest {
-        domain_id: String::from(TEST_DOMAIN),
-        room_name: String::from(TEST_ROOM)
-    };
+    for i in 0..number_of_iterations {
+        // Stop at Ctrl-C; the connection is closed below.
+        if edge_view::shutdown::is_requested() {
+            break;
+        }
 
-    serde_json::to_string(&get_users_request).unwrap()
-}
+        event!(Level::DEBUG, "========================================");
+        event!(Level::DEBUG, "Iteration {}", i);
 
-fn build_messages_request() -> String {
-    let messages_request: GetMessagesRequest = GetMessagesRequest {
-        domain_id: String::from(TEST_DOMAIN),
-        room_name: String::from(TEST_ROOM),
-    };
+        if client_socket.is_none() {
+            let started = time::Instant::now();
 
-    serde_json::to_string(&messages_request).unwrap()
-}
+            client_socket = edge_view::client::ws_connect(
+                args,
+                edge_view::client::SERVER_PORT,
+                Algorithm::HS256,
+                path).await.ok();
 
-fn build_search_messages_request() -> String {
-    let search_str: &str = "test_keyword";
+            handshake_time += started.elapsed();
+            connections_opened += 1;
+        }
 
-    let request: SearchMessagesRequest = SearchMessagesRequest {
-        domain_id: String::from(TEST_DOMAIN),
-        room_name: String::from(TEST_ROOM),
-        keywords: vec!(String::from(search_str)),
-    };
+        let client = match client_socket.as_mut() {
+            Some(client) => client,
+            None => {
+                event!(Level::ERROR, "No WebSocket connection.");
+                continue;
+            }
+        };
 
-    event!(Level::DEBUG, "Searching for messages containing {}", search_str);
+        let stats = client.get_ref().stats();
+        let mut retries: u32 = 0;
+        let mut backoff = time::Duration::from_millis(RATE_LIMIT_INITIAL_BACKOFF_MS);
 
-    serde_json::to_string(&request).unwrap()
-} // end build_search_messages_request
+        // Stay under the server's rate limit, and if it refuses a request
+        // anyway, back off and send it again.
+        let (sent, started) = loop {
+            rate_limiter.acquire().await;
 
-fn build_new_message_request() -> String {
-    let request: SendNewMessageRequest = SendNewMessageRequest {
-        domain_id: String::from(TEST_DOMAIN),
-        room_name: String::from(TEST_ROOM),
-        text: String::from("I'm a new message")
-    };
+            let started = time::Instant::now();
+            let sent = match client.send(Message::Text(build_request())).await {
+                Ok(()) => {
+                    stats.record_request();
+                    event!(Level::DEBUG, "Attempting to read response from {} endpoint:", path);
+                    Ok(client.next().await)
+                }
+                Err(e) => Err(e),
+            };
 
-    request.to_json()
-} // end build_new_message_request
-
-fn build_test_claim() -> EdgeViewClaims {
-    EdgeViewClaims {
-        exp:                    jsonwebtoken::get_current_timestamp() + time::Duration::from_secs(3600).as_secs(),
-        iat:                    jsonwebtoken::get_current_timestamp(),
-        auth_time:              jsonwebtoken::get_current_timestamp(),
-        jti:                    String::from("e5f3e658-629a-42ff-a63f-20a50afa61d6"),
-        iss:                    String::from("https://app.fmvedgeview.net/keycloak/auth/realms/fmv"),
-        aud:                    None,
-        sub:                    String::from("6e4b6e86-030b-41ed-90ab-c05325526a06"),
-        typ:                    String::from("Bearer"),
-        azp:                    String::from("edge-view-ui"),
-        nonce:                  String::from(Uuid::new_v4()),
-        session_state:          String::from(Uuid::new_v4()),
-        acr:                    String::from("1"),
-        allowed_origins:        vec![
-            String::from("http://0.0.0.0"),
-            String::from("https://app.fmvedgeview.net"),
-        ],
-        realm_access:           RealmAccess {
-            roles:              vec![
-                String::from("authenticated user"),
-            ],
-        },
-        resource_access:        ResourceAccess {
-            realm_management:   RealmManagement {
-                roles:          vec![String::from("view-users"),],
-            },
-            account:            Account {
-                roles:          vec![String::from("view-profile"),],
-            },
-        },
-        scope:                  String::from("openid email profile"),
-        sid:                    Uuid::new_v4(),
-        email_verified:         true,
-        name:                   String::from("Austin Farrell"),
-        preferred_username:     String::from("austin.farrell@ninehilltech.com"),
-        given_name:             String::from("Austin"),
-        family_name:            String::from("Farrell"),
-        email:                  String::from("austin.farrell@ninehilltech.com"),
-    }
-}
-
-fn build_jwt(alg: Algorithm) -> String {
-    let header = Header::new(alg);
-    let claims = build_test_claim();
-
-    // Construct the JWT.
-    let jwt = encode(
-        &header,
-        &claims,
-        &EncodingKey::from_secret("MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAzq/jsj5MTmOA9sW4YBJpv16yLPvznKLj3UqNXQ17WhukP5wu6GQyHMUSqNV8CAqGEA8TJpoQcpTCs8iaKxpfF1yORKdeuvCa/aJZpOw6TwsJZa1OWLONyJnOuPeZZNDUn+D7as+tS9ws7UP3AtROO8hkMS7+B3C90eXTWhZnkzEDSfDmfUxPMvYH/5yGUI4AtzbAGPMwiDOXOguXUSkV5TP7RXTZqrgHp3yvzBsbaWtjW9r4tfzXRHuGFXhlEgBdsBIzupaXrpfqIjHQXDhJ1NnI6KOQUTDi5t3VOhfZ8z6WXMPdqi/pvyzTenAshvoTR2rEti6KyLqwTdW6y1KFVQIDAQAB".as_ref())).unwrap();
-
-    jwt
-} // end build_jwt
-
-async fn ws_connect_send
-(
-    server_port:    u16,
-    jwt_alg:        Algorithm,
-    path:           &str,
-    message:        String,
-) -> Option<Message> {
-    let url = ("localhost", server_port);
-    let auth_token: HeaderValue = format!("Bearer {}", build_jwt(jwt_alg)).parse().unwrap();
-    
-    let mut auth_request = format!("ws://localhost:{}{}",
-            server_port,
-            path)
-        .into_client_request()
-        .unwrap();
-
-    auth_request
-        .headers_mut()
-        .insert("Authorization", auth_token);
-
-    let stream = TcpStream::connect(url).await.unwrap();
-
-    let (socket, _) = client_async(
-        auth_request,
-        stream
-    ).await.expect("Failed to connect");
-
-    std::thread::sleep(time::Duration::from_millis(3000));
-
-    let (mut write, mut read) = socket.split();
-
-    // Send the request.
-    let result = match write.send(Message::Text(message)).await {
-        Ok(()) => {
-            event!(Level::DEBUG, "Attempting to read response from {} endpoint:", path);
-            match read.next().await {
-                Some(response) => {
-                    event!(Level::DEBUG, "We received a response!");
-
-                    match response {
-                        Ok(payload) => Some(payload),
-                        Err(e) => {
-                            event!(Level::ERROR, "{}", e);
-                            None
+            match &sent {
+                Ok(Some(Ok(payload))) if is_rate_limited(payload) && retries < args.rate_limit_retries => {
+                    event!(Level::WARN,
+                        "Iteration {} was rate limited; retrying in {} ms.",
+                        i,
+                        backoff.as_millis()
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    retries += 1;
+                }
+                _ => break (sent, started),
+            }
+        };
+
+        match sent {
+            Ok(response) => {
+                match response {
+                    Some(Ok(payload)) if is_rate_limited(&payload) => {
+                        event!(Level::ERROR,
+                            "Iteration {} was still rate limited after {} retries.",
+                            i,
+                            retries
+                        );
+                    }
+                    Some(Ok(payload)) => {
+                        round_trip_time += started.elapsed();
+                        event!(Level::DEBUG, "We received a response!");
+                        event!(Level::DEBUG, "{}", payload);
+                        number_of_successes += 1;
+
+                        if compare_responses {
+                            let response: serde_json::Value = payload
+                                .to_text()
+                                .ok()
+                                .and_then(|text| serde_json::from_str(text).ok())
+                                .unwrap_or(serde_json::Value::Null);
+
+                            match &first_response {
+                                Some(first) if *first != response => {
+                                    event!(Level::ERROR,
+                                        "The response to iteration {} differs from the first response.",
+                                        i
+                                    );
+                                    responses_differing += 1;
+                                }
+                                Some(_) => {}
+                                None => first_response = Some(response),
+                            }
                         }
                     }
+                    Some(Err(e)) => {
+                        event!(Level::ERROR, "{}", e);
+                    }
+                    None => {}
                 }
-                None => None
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Could not send the request: {}", e);
             }
         }
-        Err(e) => {
-            event!(Level::ERROR, "Could not send the request: {}", e);
-            None
-        }
-    };
 
-    let close_frame = CloseFrame {
-        code: CloseCode::Normal,
-        reason: std::borrow::Cow::Owned(String::from("Complete"))
-    };
-
-    match write.send(Message::Close(Some(close_frame))).await {
-        Ok(()) => {
-            event!(Level::DEBUG, "Successfully sent the closing frame.");
-        }
-        Err(e) => {
-            event!(Level::ERROR, "Could not send the closing frame: {}", e);
+        if args.repeat_new_connection {
+            if let Some(client) = client_socket.take() {
+                edge_view::client::ws_close(args, client).await;
+            }
         }
     }
 
-    result
-} // end ws_connect_send
+    if let Some(client) = client_socket.take() {
+        edge_view::client::ws_close(args, client).await;
+    }
 
-async fn test_send_new_message() -> bool {
-    event!(Level::INFO, "Beginning Send New Message Test.");
+    event!(Level::INFO,
+        "{} Repeat Test ({}): {} connections, average handshake {} ms, average round trip {} ms.",
+        name,
+        mode,
+        connections_opened,
+        handshake_time.as_millis() / connections_opened.max(1) as u128,
+        round_trip_time.as_millis() / number_of_successes.max(1) as u128
+    );
+
+    event!(Level::INFO,
+        "{} Repeat Test: {}/{} iterations succeeded.",
+        name,
+        number_of_successes,
+        number_of_iterations
+    );
+
+    if compare_responses {
+        event!(Level::INFO,
+            "{} of {} responses differed from the first response.",
+            responses_differing,
+            number_of_successes
+        );
+    }
 
-    let response = ws_connect_send(
-        7878,
-        Algorithm::HS256,
-        "/send",
-        build_new_message_request()).await;
+    if number_of_successes == number_of_iterations && responses_differing == 0 {
+        event!(Level::INFO, "{} Repeat Test passed!", name);
+        true
+    } else {
+        event!(Level::ERROR, "{} Repeat Test failed!", name);
+        false
+    }
+} // end repeat_test
 
-    match response {
-        Some(payload) => {
+async fn test_send_new_message_repeat(args: &cli::Args) -> bool {
+    repeat_test(
+        args,
+        "Send New Message",
+        "/send",
+        || edge_view::client::build_new_message_request_with_text(
+            &args.domain(),
+            args.room(),
+            args.message_text(),
+            args.classification.as_deref()),
+        false).await
+} // end test_send_new_message_repeat
+
+async fn test_get_users_repeat(args: &cli::Args) -> bool {
+    repeat_test(
+        args,
+        "Get Users",
+        "/users",
+        || edge_view::client::build_users_request(&args.domain(), args.room()),
+        args.check_idempotency).await
+} // end test_get_users_repeat
+
+// The environment variables the client reads, and what happens when they
+// are not set.
+const ENVIRONMENT_VARIABLES: [(&str, &str); 3] = [
+    ("RUST_LOG",            "only warnings, errors, and this summary are logged"),
+    ("EDGE_VIEW_DOMAIN",    "the domain comes from --domain or --network"),
+    ("EDGE_VIEW_ROOM",      "the room comes from --room"),
+];
+
+/*
+ * This function logs whether a .env file was loaded, and for each of the
+ * environment variables we read, whether it was resolved from the process
+ * environment, from the .env file, or left to its default.  These are
+ * logged under the "environment" target, which is shown by default.
+ */
+fn log_environment(dotenv_result: &dotenv::Result<std::path::PathBuf>, set_before_dotenv: &[bool]) {
+    match dotenv_result {
+        Ok(path) => event!(target: "environment", Level::INFO, "Loaded environment variables from {}", path.display()),
+        Err(e) => event!(target: "environment", Level::INFO, "No .env file was loaded: {}", e),
+    }
 
-            event!(Level::DEBUG, "{}", payload);
-            event!(Level::INFO, "Send New Message Test passed!");
-            true
+    for ((name, default), set_before) in ENVIRONMENT_VARIABLES.iter().zip(set_before_dotenv) {
+        if *set_before {
+            event!(target: "environment", Level::INFO, "{} resolved from the process environment.", name);
+        } else if std::env::var_os(name).is_some() {
+            event!(target: "environment", Level::INFO, "{} resolved from the .env file.", name);
+        } else {
+            event!(target: "environment", Level::INFO, "{} is not set, so {}.", name, default);
         }
-        None => {
-            event!(Level::DEBUG, "No response received.");
-            event!(Level::ERROR, "Send New Message Test Failed!");
-            false
+    }
+} // end log_environment
+
+/*
+ * This function waits for every spawned task to finish, logging any that
+ * failed.
+ */
+async fn join_tasks(tasks: &mut JoinSet<()>) {
+    while let Some(completed_task) = tasks.join_next().await {
+        match completed_task {
+            Ok(()) => {
+                event!(Level::DEBUG, "Task completed.");
+            }
+            Err(e) => {
+                event!(Level::ERROR, "A task encountered an error: {}", e);
+            }
         }
     }
+} // end join_tasks
 
+#[tokio::main]
+async fn main() {
+    // Note which variables were already set, so that we can tell which
+    // ones came from the .env file.
+    let set_before_dotenv: Vec<bool> = ENVIRONMENT_VARIABLES
+        .iter()
+        .map(|(name, _)| std::env::var_os(name).is_some())
+        .collect();
+    let dotenv_result = dotenv();
+
+    // Set up the logging subscriber.  Without RUST_LOG, warnings and errors
+    // are logged, along with the summary of the environment.
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(EnvFilter::builder()
+            .with_default_directive(LevelFilter::WARN.into())
+            .parse_lossy(std::env::var("RUST_LOG").unwrap_or(String::from("environment=info"))))
+        .init();
 
-} // end test_send_new_message
+    log_environment(&dotenv_result, &set_before_dotenv);
+    
+    let args = cli::parse_arguments();
+    let mut tasks: JoinSet<()> = JoinSet::new();
 
-async fn test_send_new_message_repeat() -> bool {
-    event!(Level::INFO, "Beginning Send New Message Repeat Test.");
+    edge_view::shutdown::handle_ctrl_c();
 
-    let number_of_iterations: i32 = 3;
-    let mut number_of_successes: i32 = 0;
+    if args.list_tests {
+        cli::list_tests(&args);
+        return;
+    }
 
-    let path = "/send";
-    let client_socket = edge_view::client::ws_connect(7878, Algorithm::HS256, path).await;
+    if let Some(path) = &args.interactive {
+        edge_view::client::interactive(&args, path).await;
+        return;
+    }
 
-    let (mut write, mut read) = client_socket.unwrap().split();
+    if args.test_get_users_and_listen {
+        event!(Level::DEBUG, "Spawning test_get_users_and_listen thread.");
+        tasks.spawn(edge_view::client::test_get_users_and_listen(args.clone()));
+    }
 
+    if let Some(endpoints) = &args.spin_client {
+        for endpoint in endpoints {
+            event!(Level::DEBUG, "Spawning {} spinning clients on {}.", args.spin_count, endpoint);
 
-    for i in 0..number_of_iterations {
-        event!(Level::DEBUG, "========================================");
-        event!(Level::DEBUG, "Iteration {}", i);
-
-        match write.send(Message::Text(build_new_message_request())).await {
-            Ok(()) => {
-                event!(Level::DEBUG, "Attempting to read response from {} endpoint:", path);
-                match read.next().await {
-                    Some(response) => {
-                        event!(Level::DEBUG, "We received a response!");
-    
-                        match response {
-                            Ok(payload) => {
-                                event!(Level::DEBUG, "{}", payload);
-                                number_of_successes += 1;
-                            }
-                            Err(e) => {
-                                event!(Level::ERROR, "{}", e);   
-                            }
-                        }
-                    }
-                    None => {}
-                }
-            }
-            Err(e) => {
-                event!(Level::ERROR, "Could not send the request: {}", e);
+            for _ in 0..args.spin_count {
+                tasks.spawn(edge_view::client::spin_client(args.clone(), endpoint.clone()));
             }
         }
     }
 
-    if number_of_successes == number_of_iterations {
-        event!(Level::INFO, "Send New Message Repeat Test passed!");
-        true
-    } else {
-        event!(Level::ERROR, "Send New Message Repeat Test failed!");
-        false
+    // The listen, search_all, and pipeline modes, the repeat tests, and the
+    // test list all count towards the exit code.
+    let mut tests_passed: usize = 0;
+    let mut tests_run: usize = 0;
+    let mut aborted = false;
+
+    if let Some(path) = &args.listen {
+        match edge_view::client::build_request_for_path(&args, path) {
+            Some(request) => {
+                let listened = edge_view::client::listen(
+                    &args,
+                    path,
+                    request,
+                    args.listen_max_messages,
+                    time::Duration::from_millis(args.listen_idle_ms)).await;
+
+                event!(Level::INFO, "Received {} messages from {}.", listened.messages.len(), path);
+                tests_passed += usize::from(listened.passed);
+            }
+            None => {
+                event!(Level::ERROR, "There is no request to listen with for {}.", path);
+            }
+        }
+        tests_run += 1;
     }
 
-}
-
-async fn test_get_users_repeat() -> bool {
-    let number_of_iterations: i32 = 3;
-    let mut number_of_successes: i32 = 0;
-    let path: &str = "/users";
-
-    event!(Level::INFO, "Beginning Get Users Repeat Test.");
-
-    let client = edge_view::client::ws_connect(7878, Algorithm::HS256, path).await;
+    if let Some(max_pages) = args.search_all {
+        match edge_view::client::build_paged_search_request(&args) {
+            Ok(request) => {
+                let sort = request.sort.clone();
+                let messages = edge_view::client::search_all(&args, "/search", request, max_pages).await;
 
-    let (mut write, mut read) = client.unwrap().split();
+                event!(Level::INFO, "Found {} messages across the search pages.", messages.len());
 
-    for i in 0..number_of_iterations {
-        event!(Level::DEBUG, "========================================");
-        event!(Level::DEBUG, "Iteration {}", i);
+                // The order has to hold from one page to the next, not
+                // only within each page.
+                let sorted = match &sort {
+                    Some(sort) => test_case::sort_order_validator(sort, &messages),
+                    None => Ok(()),
+                };
 
-        match write.send(Message::Text(get_users_message())).await {
-            Ok(()) => {
-                event!(Level::DEBUG, "Attempting to read response from {} endpoint:", path);
-                match read.next().await {
-                    Some(response) => {
-                        event!(Level::DEBUG, "We received a response!");
-    
-                        match response {
-                            Ok(payload) => {
-                                event!(Level::DEBUG, "{}", payload);
-                                number_of_successes += 1;
-                            }
-                            Err(e) => {
-                                event!(Level::ERROR, "{}", e);   
-                            }
-                        }
-                    }
-                    None => {}
+                match sorted {
+                    Ok(()) => tests_passed += 1,
+                    Err(e) => event!(Level::ERROR, "{}", e),
                 }
             }
             Err(e) => {
-                event!(Level::ERROR, "Could not send the request: {}", e);
+                event!(Level::ERROR, "Could not build the search to page through: {}", e);
             }
         }
+        tests_run += 1;
     }
 
-    let close_frame = CloseFrame {
-        code: CloseCode::Normal,
-        reason: std::borrow::Cow::Owned(String::from("Complete"))
-    };
-
-    match write.send(Message::Close(Some(close_frame))).await {
-        Ok(()) => {
-            event!(Level::DEBUG, "Successfully sent the closing frame.");
-        }
-        Err(e) => {
-            event!(Level::ERROR, "Could not send the closing frame: {}", e);
-        }
+    if let Some(count) = args.seed_room {
+        edge_view::client::seed_room(&args, count).await;
     }
 
-    if number_of_successes == number_of_iterations {
-        event!(Level::INFO, "Get Users Repeat Test passed!");
-        true
-    } else {
-        event!(Level::ERROR, "Get Users Repeat Test failed!");
-        false
+    if let Some(count) = args.pipeline {
+        tests_passed += usize::from(edge_view::client::pipeline_test(&args, count).await);
+        tests_run += 1;
     }
 
-} // end test_get_users
-
-async fn test_get_messages() -> bool {
-    event!(Level::INFO, "Beginning Get Messages Test.");
-
-    let response = ws_connect_send(
-        7878,
-        Algorithm::HS256,
-        "/messages",
-        build_messages_request()).await;
-
-    match response {
-        Some(payload) => {
-
-            event!(Level::DEBUG, "{}", payload);
-            event!(Level::INFO, "Get Messages Test passed!");
-            true
-        }
-        None => {
-            event!(Level::DEBUG, "No response received.");
-            event!(Level::ERROR, "Get Messages Test Failed!");
-            false
-        }
+    if args.test_get_users_repeat {
+        tests_passed += usize::from(test_get_users_repeat(&args).await);
+        tests_run += 1;
     }
-} // end test_get_messages
 
-async fn test_search_messages() -> bool {
-    event!(Level::INFO, "Beginning Search Messages Test.");
-
-    let response = ws_connect_send(
-        7878,
-        Algorithm::HS256,
-        "/search",
-        build_search_messages_request()).await;
-
-    match response {
-        Some(payload) => {
-            
+    if args.test_send_message_repeat {
+        tests_passed += usize::from(test_send_new_message_repeat(&args).await);
+        tests_run += 1;
+    }
 
+    let test_list = cli::process_arguments(&args);
 
-            event!(Level::DEBUG, "{}", payload);
-            event!(Level::INFO, "Search Messages Test passed!");
-            true
-        }
-        None => {
-            event!(Level::DEBUG, "No response received.");
-            event!(Level::ERROR, "Search Messages Test Failed!");
-            false
-        }
+    if test_list.is_empty() && args.require_tests {
+        event!(Level::ERROR, "No tests were selected, but --require_tests was given.");
+        std::process::exit(test_case::NO_TESTS_EXIT_CODE);
     }
-} // end test_search_messages
 
-async fn test(stream: TcpStream) {
+    // The modes above are useful on their own, so a run made up of only
+    // those doesn't need a "0/0" summary of tests it never ran.
+    if !test_list.is_empty() {
+        let mut iterations: Vec<Vec<test_case::TestResult>> = Vec::new();
 
-    
-    event!(Level::DEBUG, "Creating the request");
-    let socket: Option<WebSocketStream<TcpStream>> = match format!("ws://localhost:{}{}", 7878, "/users").into_client_request() {
-        Ok(mut auth_request) => {
+        for iteration in 1..=args.iterations {
+            if args.iterations > 1 {
+                event!(Level::INFO, "Beginning iteration {}/{}.", iteration, args.iterations);
+            }
 
-            event!(Level::DEBUG, "Building the JWT");
-            match format!("Bearer {}", build_jwt(Algorithm::HS256)).parse::<HeaderValue>() {
+            let run = test_case::run_test_list(&args, test_list.clone()).await;
 
-                Ok(auth_token) => {
+            tests_passed += run.results.iter().filter(|result| result.passed).count();
+            tests_run += test_list.len();
+            aborted = run.aborted;
+            iterations.push(run.results);
 
-                    event!(Level::DEBUG, "Inserting the header");
-                    auth_request
-                        .headers_mut()
-                        .insert("Authorization", auth_token);
+            if edge_view::shutdown::is_requested() {
+                break;
+            }
+        }
 
+        if args.iterations > 1 {
+            summary::report_iterations(&iterations);
+        }
 
-                    event!(Level::DEBUG, "Establishing the WebSocket handshake with the server");
-                    match client_async(auth_request, stream).await {
-                        Ok((socket, response)) => {
-                            event!(Level::DEBUG, "Connected and retreived the WebSocket stream");
-                            Some(socket)
+        if args.bench_report.is_some() || args.baseline.is_some() {
+            let report = bench_report::BenchReport::new(iterations.iter().flatten());
 
-                        }
-                        Err(e) => {
-                            event!(Level::ERROR, "Could not complete the WebSocket handshake: {}", e);
-                            None
-                        }
-                    }
-                }
-                Err(e) => {
-                    event!(Level::ERROR, "Could not parse the JWT into a HeaderValue: {}", e);
-                    None
+            if let Some(path) = &args.bench_report {
+                match bench_report::write_bench_report(path, &report) {
+                    Ok(()) => event!(Level::INFO, "Wrote the benchmark report to {}.", path),
+                    Err(e) => event!(Level::ERROR, "{}", e),
                 }
             }
-        }
-        Err(e) => {
-            event!(Level::ERROR, "Could not create the request: {}", e);
-            None
-        }
-    };
 
-    match socket {
-        Some(socket) => {
+            // A regression fails the run like a failed test would.
+            if let Some(path) = &args.baseline {
+                match bench_report::read_bench_report(path) {
+                    Ok(baseline) => {
+                        let regressions = bench_report::compare_with_baseline(&baseline, &report, args.regression_threshold);
 
-            event!(Level::DEBUG, "Splitting the WebSocket stream.");
-            let (mut write, mut read) = socket.split();
-
-            // Send the request.
-            event!(Level::DEBUG, "Sending the Get Users request.");
-            let result = match write.send(Message::Text(edge_view::client::build_users_request())).await {
-                Ok(()) => {
-                    event!(Level::DEBUG, "Attempting to read response from {} endpoint:", "/users");
-                    match read.next().await {
-                        Some(response) => {
-                            event!(Level::DEBUG, "We received a response!");
-        
-                            match response {
-                                Ok(payload) => Some(payload),
-                                Err(e) => {
-                                    event!(Level::ERROR, "{}", e);
-                                    None
-                                }
-                            }
+                        for regression in &regressions {
+                            event!(Level::ERROR, "{}", regression);
                         }
-                        None => None
+                        tests_passed += usize::from(regressions.is_empty());
                     }
+                    Err(e) => event!(Level::ERROR, "{}", e),
                 }
-                Err(e) => {
-                    event!(Level::ERROR, "Could not send the request: {}", e);
-                    None
-                }
-            };
-        
-            let close_frame = CloseFrame {
-                code: CloseCode::Normal,
-                reason: std::borrow::Cow::Owned(String::from("Complete"))
-            };
-        
-            match write.send(Message::Close(Some(close_frame))).await {
-                Ok(()) => {
-                    event!(Level::DEBUG, "Successfully sent the closing frame.");
-                }
-                Err(e) => {
-                    event!(Level::ERROR, "Could not send the closing frame: {}", e);
-                }
+                tests_run += 1;
             }
         }
-        None => {
-            event!(Level::ERROR, "Could not create the socket.");
-        }
     }
-}
 
-#[tokio::main]
-async fn main() {
-    let mut tests_passed: i32 = 0;
-    let mut total_tests: i32 = 0;
-
-    // Set up the logging subscriber.
-    dotenv().ok();
-    tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::from_default_env())
-        .init();
-    
-     let mut tasks = cli::process_arguments();
-
-    // while let Some(completed_task) = tasks.join_next().await {
-    //     match completed_task {
-    //         Ok(()) => {
-    //             event!(Level::DEBUG, "Task completed.");
-    //         }
-    //         Err(e) => {
-    //             event!(Level::ERROR, "A task encountered an error: {}", e);
-    //         }
-    //     }
-    // }
-
-
-    // let (socket, _) = client_async(
-    //     auth_request,
-    //     stream
-    // ).await.expect("Failed to connect");
+    // Spinning clients and the listen test run until the server closes
+    // their connections, so let them be stopped with Ctrl-C.
+    if args.spin_client.is_some() || args.test_get_users_and_listen {
+        event!(Level::INFO, "Press Ctrl-C to stop the background clients.");
+    }
 
+    // After Ctrl-C the tasks close their connections and finish on their
+    // own.  Any that haven't within the grace period are cut off.
+    let grace_period_expired = async {
+        edge_view::shutdown::requested().await;
+        tokio::time::sleep(time::Duration::from_millis(edge_view::shutdown::SHUTDOWN_GRACE_PERIOD_MS)).await;
+    };
 
+    tokio::select! {
+        _ = join_tasks(&mut tasks) => {}
+        _ = grace_period_expired => {
+            event!(Level::WARN, "Stopping the background clients that did not close in time.");
+            tasks.shutdown().await;
+        }
+    }
 
+    if args.connection_stats {
+        edge_view::connection_stats::log_connection_stats();
+    }
 
-    //======================================================================
-    // Send New Message Endpoint
-    // total_tests += 1;
-    // if test_send_new_message().await { tests_passed += 1; }
-    
-    // total_tests += 1;
-    // if test_send_new_message_repeat().await { tests_passed += 1; }
+    // An abort also asks the connections to close, so check for it before
+    // Ctrl-C.
+    if aborted {
+        std::process::exit(test_case::ABORTED_EXIT_CODE);
+    }
 
-    //======================================================================
-    //Get Users Endpoint
-    //total_tests += 1;
-    if test_get_users_repeat().await { tests_passed += 1; }
-    
-    //======================================================================
-    // Get Messages Endpoint
-    // total_tests += 1;
-    // if test_get_messages().await { tests_passed += 1; }
+    // Tests that Ctrl-C kept from running would otherwise count as failed.
+    if edge_view::shutdown::is_requested() {
+        std::process::exit(test_case::INTERRUPTED_EXIT_CODE);
+    }
 
-    //======================================================================
-    // Search Messages Endpoint
-    // total_tests += 1;
-    // if test_search_messages().await { tests_passed += 1; }
+    // Let CI treat the run as a gate: fail when any test failed in any
+    // iteration, or when no tests ran at all.  A run made up only of the
+    // modes that aren't tests, such as --seed_room, has nothing to fail.
+    let ran_other_modes = args.spin_client.is_some()
+        || args.test_get_users_and_listen
+        || args.seed_room.is_some();
 
-    event!(Level::INFO, "Tests Passed: {}/{}", tests_passed, total_tests);
+    if tests_passed < tests_run || (tests_run == 0 && !ran_other_modes) {
+        std::process::exit(test_case::FAILED_TESTS_EXIT_CODE);
+    }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use crate::chatsurfer::messages::{
     ChatMessageSchema,
     ErrorCode400,
+    TimeFilterRequest,
 };
 //use http::StatusCode;
 use serde::{ Deserialize, Serialize };
@@ -131,11 +132,37 @@ pub struct GetMessagesRequest {
 
 /// The GetMessagesResponse structure defines the response that will be sent to
 /// Edge View for a successful Get Messages request.
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct GetMessagesResponse {
     pub classification: String,
     pub messages:       Vec<ChatMessageSchema>,
 }
+
+impl fmt::Display for GetMessagesResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl GetMessagesResponse {
+    /*
+     * This method constructs an empty GetMessagesResponse, for
+     * use as a test fixture.
+     */
+    #[cfg(test)]
+    pub fn new() -> GetMessagesResponse {
+        GetMessagesResponse::default()
+    }
+
+    /*
+     * This method attempts to construct a GetMessagesResponse from the
+     * given JSON string.
+     */
+    pub fn try_from_json(json: String) -> Result<GetMessagesResponse, serde_json::Error> {
+        serde_json::from_str(&json)
+    }
+}
+
 /// The GetMessagesResponseTypes enumeration defines the types of responses
 /// that we well send back to Edge View for the Get Messages endpoint.
 pub enum GetMessagesResponseTypes {
@@ -164,6 +191,14 @@ pub struct SearchMessagesRequest {
     #[serde(rename = "roomName")]
     pub room_name:   String,
     pub keywords:   Vec<String>,
+
+    // Whether the matching messages should include their geo-tags.
+    #[serde(rename = "requestGeoTags", skip_serializing_if = "Option::is_none")]
+    pub request_geo_tags: Option<bool>,
+
+    // Limits the search to messages sent within a window of time.
+    #[serde(rename = "timeFilter", default, skip_serializing_if = "Option::is_none")]
+    pub time_filter: Option<TimeFilterRequest>,
 }
 
 //==============================================================================
@@ -172,9 +207,47 @@ pub struct SearchMessagesRequest {
 
 /// The SearchMessagesResponse structure defines the response that will be
 /// sent to Edge View for a successful Search Messages request.
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct SearchMessagesResponse {
     pub messages:   Vec<ChatMessageSchema>,
+
+    // The total number of matches, which can exceed the number of messages
+    // returned in this page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total:      Option<usize>,
+}
+
+impl fmt::Display for SearchMessagesResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_json())
+    }
+}
+
+impl SearchMessagesResponse {
+    /*
+     * This method constructs an empty SearchMessagesResponse, for
+     * use as a test fixture.
+     */
+    #[cfg(test)]
+    pub fn new() -> SearchMessagesResponse {
+        SearchMessagesResponse::default()
+    }
+
+    /*
+     * This method constructs a JSON string from the SearchMessagesResponse's
+     * fields.
+     */
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /*
+     * This method attempts to construct a SearchMessagesResponse from the
+     * given JSON string.
+     */
+    pub fn try_from_json(json: String) -> Result<SearchMessagesResponse, serde_json::Error> {
+        serde_json::from_str(&json)
+    }
 }
 
 // #############################################################################
@@ -218,7 +291,7 @@ impl fmt::Display for GetUsersRequest {
 
 /// The GetUsersResponse structure defines the response that will be sent to
 /// Edge View for a successful Get Users request.
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct GetUsersResponse {
     #[serde(rename = "userNames")]
     pub user_names: Vec<String>
@@ -231,6 +304,15 @@ impl fmt::Display for GetUsersResponse {
 }
 
 impl GetUsersResponse {
+    /*
+     * This method constructs an empty GetUsersResponse, for
+     * use as a test fixture.
+     */
+    #[cfg(test)]
+    pub fn new() -> GetUsersResponse {
+        GetUsersResponse::default()
+    }
+
     /*
      * This method constructs a JSON string from the GetUsersResponse's
      * fields.
@@ -238,6 +320,14 @@ impl GetUsersResponse {
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /*
+     * This method attempts to construct a GetUsersResponse from the
+     * given JSON string.
+     */
+    pub fn try_from_json(json: String) -> Result<GetUsersResponse, serde_json::Error> {
+        serde_json::from_str(&json)
+    }
 }
 
 /// The GetUserResponseTypes enumeration defines the types of responses
@@ -269,6 +359,11 @@ pub struct SendNewMessageRequest {
     #[serde(rename = "roomName")]
     pub room_name:  String,
     pub text:       String,
+
+    // The marking for the message.  When left out, the server applies its
+    // own default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<String>,
 }
 
 impl fmt::Display for SendNewMessageRequest {
@@ -289,14 +384,248 @@ impl SendNewMessageRequest {
 
 /// The SendNewMessageResponse structure defines the response that will be
 /// send to Edge View for a successful Send Message request.
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct SendNewMessageResponse {
-    pub message: String
+    // Servers that reply with only the new message's id leave this out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+
+    // The id the server assigned to the new message.  Servers that relay
+    // ChatSurfer's 204 No Content reply leave this out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+}
+
+impl fmt::Display for SendNewMessageResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_json())
+    }
+}
+
+impl SendNewMessageResponse {
+    /*
+     * This method constructs an empty SendNewMessageResponse, for
+     * use as a test fixture.
+     */
+    #[cfg(test)]
+    pub fn new() -> SendNewMessageResponse {
+        SendNewMessageResponse::default()
+    }
+
+    /*
+     * This method constructs a JSON string from the SendNewMessageResponse's
+     * fields.
+     */
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /*
+     * This method attempts to construct a SendNewMessageResponse from the
+     * given JSON string.
+     */
+    pub fn try_from_json(json: String) -> Result<SendNewMessageResponse, serde_json::Error> {
+        serde_json::from_str(&json)
+    }
 }
+
 /// The SendNewMessageResponseTypes enumeration defines the types of
 /// responses that we can send back to Edge View for the Send Message
 /// endpoint.
 pub enum SendNewMessageResponseTypes {
     SendNewMessageResponse  { response: SendNewMessageResponse },
     Error                   { response: Error },
-}
\ No newline at end of file
+}
+
+// #############################################################################
+// #############################################################################
+//                              Any Endpoint
+// #############################################################################
+// #############################################################################
+
+/// The EdgeViewResponseTypes enumeration holds a response from any of the
+/// Edge View endpoints, parsed as the type that endpoint sends.
+pub enum EdgeViewResponseTypes {
+    GetMessagesResponse     { response: GetMessagesResponse },
+    GetUsersResponse        { response: GetUsersResponse },
+    SearchMessagesResponse  { response: SearchMessagesResponse },
+    SendNewMessageResponse  { response: SendNewMessageResponse },
+    Error                   { response: Error },
+}
+
+/*
+ * Implement the trait fmt::Display for the enum EdgeViewResponseTypes so
+ * that a response from any endpoint can be logged the same way.
+ */
+impl fmt::Display for EdgeViewResponseTypes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EdgeViewResponseTypes::GetMessagesResponse { response }     => write!(f, "{}", response),
+            EdgeViewResponseTypes::GetUsersResponse { response }        => write!(f, "{}", response),
+            EdgeViewResponseTypes::SearchMessagesResponse { response }  => write!(f, "{}", response),
+            EdgeViewResponseTypes::SendNewMessageResponse { response }  => write!(f, "{}", response),
+            EdgeViewResponseTypes::Error { response } => {
+                write!(f, "error {}: {}", response.code, response.message)
+            }
+        }
+    }
+}
+
+impl EdgeViewResponseTypes {
+    /*
+     * This method parses a response from the endpoint at the given path.
+     * A payload with the shape of an Error is parsed as one first, since
+     * the looser response types would otherwise accept it too.
+     */
+    pub fn try_from_json(path: &str, json: String) -> Result<EdgeViewResponseTypes, String> {
+        if let Ok(response) = serde_json::from_str::<Error>(&json) {
+            return Ok(EdgeViewResponseTypes::Error { response });
+        }
+
+        let parsed = match path {
+            "/messages" => GetMessagesResponse::try_from_json(json)
+                .map(|response| EdgeViewResponseTypes::GetMessagesResponse { response }),
+            "/users"    => GetUsersResponse::try_from_json(json)
+                .map(|response| EdgeViewResponseTypes::GetUsersResponse { response }),
+            "/search"   => SearchMessagesResponse::try_from_json(json)
+                .map(|response| EdgeViewResponseTypes::SearchMessagesResponse { response }),
+            "/send"     => SendNewMessageResponse::try_from_json(json)
+                .map(|response| EdgeViewResponseTypes::SendNewMessageResponse { response }),
+            _ => return Err(format!("There is no response type for the {} endpoint.", path)),
+        };
+
+        parsed.map_err(|e| format!("Response from {} is not a valid response: {}", path, e))
+    } // end try_from_json
+} // end EdgeViewResponseTypes
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Search Messages response as Edge View receives it.
+    const SEARCH_RESPONSE_JSON: &str = r#"{
+        "messages": [{
+            "classification": "UNCLASSIFIED",
+            "domainId": "chatsurferxmppunclass",
+            "id": "6e4b6e86-030b-41ed-90ab-c05325526a01",
+            "roomName": "edge-view-test-room",
+            "sender": "test.user",
+            "text": "Convoy departed from Norfolk.",
+            "threadId": "6e4b6e86-030b-41ed-90ab-c05325526a02",
+            "timestamp": "2024-05-01T12:00:00.000Z",
+            "userId": "6e4b6e86-030b-41ed-90ab-c05325526a03"
+        }],
+        "total": 12
+    }"#;
+
+    #[test]
+    fn search_response_round_trips_through_display() {
+        let response = SearchMessagesResponse::try_from_json(String::from(SEARCH_RESPONSE_JSON)).unwrap();
+
+        assert_eq!(response.messages.len(), 1);
+        assert_eq!(response.messages[0].text, "Convoy departed from Norfolk.");
+        assert_eq!(response.total, Some(12));
+
+        let sent: serde_json::Value = serde_json::from_str(SEARCH_RESPONSE_JSON).unwrap();
+        let displayed: serde_json::Value = serde_json::from_str(&response.to_string()).unwrap();
+        assert_eq!(displayed, sent);
+
+        let reparsed = SearchMessagesResponse::try_from_json(response.to_string()).unwrap();
+        assert_eq!(reparsed.to_json(), response.to_json());
+    }
+
+    #[test]
+    fn search_endpoint_parses_as_a_search_messages_response() {
+        let parsed = EdgeViewResponseTypes::try_from_json("/search", String::from(SEARCH_RESPONSE_JSON));
+
+        assert!(matches!(parsed, Ok(EdgeViewResponseTypes::SearchMessagesResponse { .. })));
+    }
+
+    #[test]
+    fn error_response_is_logged_by_code_and_message() {
+        let parsed = EdgeViewResponseTypes::try_from_json("/send",
+            String::from(r#"{ "classification": "UNCLASSIFIED", "code": 400, "message": "Bad request." }"#)).unwrap();
+
+        assert_eq!(parsed.to_string(), "error 400: Bad request.");
+    }
+
+    // Serializes a value, parses it back, and serializes it again, giving
+    // both serializations so that a field lost or renamed on the way shows.
+    fn round_trip<T: Serialize + serde::de::DeserializeOwned>(value: &T) -> (String, String) {
+        let serialized = serde_json::to_string(value).unwrap();
+        let reparsed: T = serde_json::from_str(&serialized).unwrap();
+
+        (serialized, serde_json::to_string(&reparsed).unwrap())
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let get_messages = GetMessagesRequest {
+            domain_id:  String::from("chatsurferxmppunclass"),
+            room_name:  String::from("edge-view-test-room"),
+        };
+        let get_users = GetUsersRequest {
+            domain_id:  String::from("chatsurferxmppunclass"),
+            room_name:  String::from("edge-view-test-room"),
+        };
+        let search = SearchMessagesRequest {
+            domain_id:          String::from("chatsurferxmppunclass"),
+            room_name:          String::from("edge-view-test-room"),
+            keywords:           vec!(String::from("convoy"), String::from("norfolk")),
+            request_geo_tags:   Some(true),
+            time_filter:        None,
+        };
+        let send = SendNewMessageRequest {
+            domain_id:      String::from("chatsurferxmppunclass"),
+            room_name:      String::from("edge-view-test-room"),
+            text:           String::from("Convoy departed from Norfolk."),
+            classification: Some(String::from("UNCLASSIFIED")),
+        };
+
+        for (serialized, reserialized) in [round_trip(&get_messages), round_trip(&get_users), round_trip(&search), round_trip(&send)] {
+            assert_eq!(reserialized, serialized);
+            assert!(serialized.contains(r#""domainId":"chatsurferxmppunclass""#));
+            assert!(serialized.contains(r#""roomName":"edge-view-test-room""#));
+        }
+
+        assert!(round_trip(&search).0.contains(r#""requestGeoTags":true"#));
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        let get_messages = GetMessagesResponse {
+            classification: String::from("UNCLASSIFIED"),
+            messages:       vec!(ChatMessageSchema::test("Convoy departed from Norfolk.")),
+        };
+        let get_users = GetUsersResponse {
+            user_names: vec!(String::from("test.user"), String::from("other.user")),
+        };
+        let search = SearchMessagesResponse {
+            messages:   vec!(ChatMessageSchema::test("Convoy departed from Norfolk.")),
+            total:      Some(12),
+        };
+        let send = SendNewMessageResponse {
+            message:    Some(String::from("Message sent.")),
+            id:         Some(Uuid::parse_str("6e4b6e86-030b-41ed-90ab-c05325526a01").unwrap()),
+        };
+        let error = Error::new_unclassified_message("Room not found.");
+
+        for (serialized, reserialized) in [round_trip(&get_messages), round_trip(&get_users), round_trip(&search), round_trip(&send), round_trip(&error)] {
+            assert_eq!(reserialized, serialized);
+        }
+
+        assert!(round_trip(&get_users).0.contains(r#""userNames":["test.user","other.user"]"#));
+    }
+
+    #[test]
+    fn optional_fields_are_left_out_when_empty() {
+        assert_eq!(SendNewMessageResponse::new().to_json(), "{}");
+        assert_eq!(SearchMessagesResponse::new().to_json(), r#"{"messages":[]}"#);
+    }
+
+    #[test]
+    fn new_responses_are_empty() {
+        assert!(GetMessagesResponse::new().messages.is_empty());
+        assert_eq!(GetUsersResponse::new().to_json(), r#"{"userNames":[]}"#);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::test_case::TestResult;
+use clap::ValueEnum;
+use serde::Serialize;
+use tracing::{event, Level};
+
+/// The SummaryFormat enumeration lists the ways the results of a run can be
+/// rendered with --summary_format.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+pub enum SummaryFormat {
+    // The "Tests Passed: X/Y" log line.
+    Text,
+
+    // An aligned plain text table of every test.
+    Table,
+
+    // A JSON document, for other tools to consume.
+    Json,
+
+    // A Markdown table, for pasting into a PR or issue.
+    Markdown,
+}
+
+/// The SummaryFormatter trait is implemented once for each SummaryFormat.
+/// Every formatter shows the same data: each test's name, outcome, and
+/// latency, and the overall tally.
+pub trait SummaryFormatter {
+    fn render(&self, results: &[TestResult], total_tests: usize) -> String;
+}
+
+fn tests_passed(results: &[TestResult]) -> usize {
+    results.iter().filter(|result| result.passed).count()
+}
+
+fn outcome(result: &TestResult) -> String {
+    match (&result.passed, &result.failure) {
+        (true, _) => String::from("PASS"),
+        (false, Some(failure)) => format!("FAIL: {}", failure),
+        (false, None) => String::from("FAIL"),
+    }
+}
+
+//==============================================================================
+// Formatters
+//==============================================================================
+
+pub struct TextSummary;
+
+impl SummaryFormatter for TextSummary {
+    fn render(&self, results: &[TestResult], total_tests: usize) -> String {
+        format!("Tests Passed: {}/{}", tests_passed(results), total_tests)
+    }
+}
+
+pub struct TableSummary;
+
+impl SummaryFormatter for TableSummary {
+    fn render(&self, results: &[TestResult], total_tests: usize) -> String {
+        let name_width = results
+            .iter()
+            .map(|result| result.name.len())
+            .chain(std::iter::once("Test".len()))
+            .max()
+            .unwrap_or_default();
+
+        let mut table = format!("{:<name_width$}  {:>12}  {}\n", "Test", "Latency (ms)", "Outcome");
+
+        for result in results {
+            table += &format!("{:<name_width$}  {:>12}  {}\n",
+                result.name,
+                result.latency.as_millis(),
+                outcome(result));
+        }
+
+        table + &TextSummary.render(results, total_tests)
+    }
+}
+
+pub struct JsonSummary;
+
+impl SummaryFormatter for JsonSummary {
+    fn render(&self, results: &[TestResult], total_tests: usize) -> String {
+        let tests: Vec<serde_json::Value> = results
+            .iter()
+            .map(|result| serde_json::json!({
+                "name":         result.name,
+                "passed":       result.passed,
+                "failure":      result.failure,
+                "latency_ms":   result.latency.as_millis() as u64,
+            }))
+            .collect();
+
+        serde_json::json!({
+            "tests":    tests,
+            "passed":   tests_passed(results),
+            "total":    total_tests,
+        }).to_string()
+    }
+}
+
+pub struct MarkdownSummary;
+
+impl SummaryFormatter for MarkdownSummary {
+    fn render(&self, results: &[TestResult], total_tests: usize) -> String {
+        let mut table = String::from("| Test | Outcome | Latency (ms) |\n|---|---|---:|\n");
+
+        for result in results {
+            // Keep pipes in failure reasons from breaking the table.
+            table += &format!("| {} | {} | {} |\n",
+                result.name,
+                outcome(result).replace('|', "\\|"),
+                result.latency.as_millis());
+        }
+
+        table + &format!("\n**{}**", TextSummary.render(results, total_tests))
+    }
+}
+
+/*
+ * This function renders the results of a run in the given format and
+ * prints it to stdout, so that it is shown whatever the log level and can
+ * be copied or piped on its own.
+ */
+pub fn report_summary(format: SummaryFormat, results: &[TestResult], total_tests: usize) {
+    let formatter: Box<dyn SummaryFormatter> = match format {
+        SummaryFormat::Text     => Box::new(TextSummary),
+        SummaryFormat::Table    => Box::new(TableSummary),
+        SummaryFormat::Json     => Box::new(JsonSummary),
+        SummaryFormat::Markdown => Box::new(MarkdownSummary),
+    };
+
+    println!("{}", formatter.render(results, total_tests));
+} // end report_summary
+
+/*
+ * This function reports, for a run repeated with --iterations, how often
+ * each test passed.  A test that neither always passed nor always failed
+ * is flagged as flaky.
+ */
+pub fn report_iterations(iterations: &[Vec<TestResult>]) {
+    // Tally each test in the order it first appeared, as (name, passed, ran).
+    let mut tallies: Vec<(&str, usize, usize)> = Vec::new();
+
+    for result in iterations.iter().flatten() {
+        let index = match tallies.iter().position(|(name, _, _)| *name == result.name) {
+            Some(index) => index,
+            None => {
+                tallies.push((&result.name, 0, 0));
+                tallies.len() - 1
+            }
+        };
+
+        tallies[index].2 += 1;
+
+        if result.passed {
+            tallies[index].1 += 1;
+        }
+    }
+
+    event!(Level::INFO, "Pass rates over {} iterations:", iterations.len());
+
+    let mut flaky_tests: usize = 0;
+
+    for (name, passed, ran) in &tallies {
+        let pass_rate = 100.0 * *passed as f64 / *ran as f64;
+
+        if *passed > 0 && passed < ran {
+            flaky_tests += 1;
+            event!(Level::WARN, "    {}: {}/{} ({:.0}%) FLAKY", name, passed, ran, pass_rate);
+        } else {
+            event!(Level::INFO, "    {}: {}/{} ({:.0}%)", name, passed, ran, pass_rate);
+        }
+    }
+
+    event!(Level::INFO, "Flaky tests: {}/{}", flaky_tests, tallies.len());
+} // end report_iterations
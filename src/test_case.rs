@@ -0,0 +1,1473 @@
+use crate::chatsurfer::messages::{
+    ChatMessageSchema,
+    ErrorCode400,
+    GeoTagSchema,
+    SortDirection,
+    SortField,
+    SortFilter,
+};
+use crate::cli::Args;
+use crate::field_coverage::FieldCoverage;
+use crate::json_report::write_json_report;
+use crate::junit::write_junit_report;
+use crate::summary::report_summary;
+use crate::edge_view::client::{
+    check_payload,
+    close_code_and_reason,
+    ConnectError,
+    build_messages_request,
+    debug,
+    error,
+    MAX_JSON_DEPTH,
+    PayloadCheck,
+    SERVER_PORT,
+    ws_connect_send,
+    ws_connect_send_until_close,
+};
+use crate::edge_view::connection_pool::ConnectionPool;
+use crate::edge_view::shutdown;
+use crate::messages::{
+    self,
+    GetMessagesResponse,
+    GetUsersResponse,
+    SearchMessagesResponse,
+    SendNewMessageRequest,
+    SendNewMessageResponse,
+};
+use jsonwebtoken::Algorithm;
+use serde::de::DeserializeOwned;
+use tokio_tungstenite::tungstenite::{
+    http::{ StatusCode, header::WWW_AUTHENTICATE },
+    protocol::{ Message, frame::coding::CloseCode },
+};
+use std::{
+    cmp::Ordering,
+    collections::{ HashMap, VecDeque },
+    sync::Arc,
+};
+use time::{ format_description::well_known::Iso8601, OffsetDateTime };
+use tokio::task::{ self, JoinSet };
+use tracing::{event, Level};
+
+// The exit code used when --require_tests is given but no tests were
+// selected.
+pub const NO_TESTS_EXIT_CODE: i32 = 2;
+
+// The exit code used when any selected test failed or none of them ran.
+pub const FAILED_TESTS_EXIT_CODE: i32 = 1;
+
+// The exit code used when --abort_after_failures or --fail_fast stops the
+// run early.
+pub const ABORTED_EXIT_CODE: i32 = 3;
+
+// The exit code used when --payload_file does not hold valid JSON.
+pub const INVALID_PAYLOAD_EXIT_CODE: i32 = 4;
+
+// The exit code used when the run is stopped with Ctrl-C, following the
+// shell's convention for SIGINT.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+// The secret used to sign the JWT for the Wrong Key test.
+const WRONG_JWT_SECRET: &str = "this-is-not-the-server-secret";
+
+// How many of the most recent failures are reported when the run is
+// aborted.
+const RECENT_FAILURES_REPORTED: usize = 5;
+
+// The largest difference, in degrees or confidence units, that we accept
+// between a number the server sent and the value GeoTagSchema stores for
+// it.  1e-6 degrees is roughly 0.1 meters.
+const PRECISION_LOSS_THRESHOLD: f64 = 1e-6;
+
+//==============================================================================
+// struct TestCase
+//==============================================================================
+
+/// The TestCase structure describes a single request that we send to one of
+/// the Edge View endpoints, along with the validator that decides whether
+/// the response we get back is acceptable.
+#[derive(Clone)]
+pub struct TestCase {
+    pub name:           String,
+    pub server_path:    String,
+    pub jwt_header_alg: Algorithm,
+    pub request:        String,
+    // Validators receive the request that was sent along with the response,
+    // so that they can check the response against it.
+    pub validator:      Validator,
+
+    // What else the test checks beyond its validator.
+    pub kind:           TestKind,
+}
+
+// A validator can close over expectations of its own, and is shared so
+// that a TestCase can be cloned for --iterations.
+pub type Validator = Arc<dyn Fn(&str, String) -> Result<(), String> + Send + Sync>;
+
+/// The TestKind enumeration lists the kinds of TestCase, each of which
+/// checks something of its own once the validator has passed, or expects
+/// the server to refuse the request altogether.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestKind {
+    // A request whose response only has to pass the validator.
+    Plain,
+
+    // A search, which --assert_total_ge and --assert_total_eq apply to.
+    Search,
+
+    // A Get Users request, which --expect_min_users applies to.
+    UserList,
+
+    // A sent message whose text should then be found among the room's
+    // messages.
+    RoundTrip,
+
+    // A request made with a bad JWT, which the server must refuse.  With a
+    // close code, it must do so by closing the connection with that code
+    // in place of sending a response.
+    Rejected { token: BadToken, close_code: Option<CloseCode> },
+}
+
+/// The BadToken enumeration lists the kinds of JWT a Rejected TestCase
+/// connects with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BadToken {
+    // A token that expired an hour ago.
+    Expired,
+
+    // A token signed with a secret the server doesn't know.
+    WrongKey,
+}
+
+// #############################################################################
+// #############################################################################
+//                                 Validators
+// #############################################################################
+// #############################################################################
+
+/*
+ * This function checks the invariants of every geo-tag attached to the given
+ * chat messages: the confidence must be within [0.0, 1.0], the anchor must
+ * be a well-ordered range within the message text, and the location must be
+ * on the globe.  Every problem found is reported.
+ */
+fn geo_tags_validator(messages: &[ChatMessageSchema]) -> Result<(), String> {
+    let mut problems: Vec<String> = Vec::new();
+
+    for message in messages {
+        let text_length = message.text.chars().count() as i64;
+
+        for geo_tag in message.geoTags.iter().flatten() {
+            if !(0.0..=1.0).contains(&geo_tag.confidence) {
+                problems.push(format!("Message {}: geo-tag confidence {} is outside [0.0, 1.0].",
+                    message.id,
+                    geo_tag.confidence));
+            }
+
+            if geo_tag.anchorStart > geo_tag.anchorEnd {
+                problems.push(format!("Message {}: geo-tag anchorStart {} is after anchorEnd {}.",
+                    message.id,
+                    geo_tag.anchorStart,
+                    geo_tag.anchorEnd));
+            }
+
+            if geo_tag.anchorStart < 0 || geo_tag.anchorEnd > text_length {
+                problems.push(format!("Message {}: geo-tag anchor {}..{} is outside the {} character message text.",
+                    message.id,
+                    geo_tag.anchorStart,
+                    geo_tag.anchorEnd,
+                    text_length));
+            }
+
+            if let Err(e) = geo_tag.location.validate() {
+                problems.push(format!("Message {}: geo-tag location {}", message.id, e));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("; "))
+    }
+} // end geo_tags_validator
+
+/*
+ * This function accepts any response.  It is used for ad-hoc payloads from
+ * --payload_file, where all we can check is that the response is JSON, and
+ * run_test has already done that by the time a validator is called.
+ */
+pub fn any_json_validator(_request: &str, _response: String) -> Result<(), String> {
+    Ok(())
+} // end any_json_validator
+
+/*
+ * This function checks the response to a request made with an expired or
+ * wrongly signed JWT, which should be a 401 or 403 error rather than a normal response.
+ */
+pub fn rejected_token_validator(_request: &str, response: String) -> Result<(), String> {
+    let code = serde_json::from_str::<serde_json::Value>(&response)
+        .ok()
+        .and_then(|value| value["code"].as_i64());
+
+    match code {
+        Some(401) | Some(403) => {
+            event!(Level::INFO, "The token was rejected with a {}.", code.unwrap());
+            Ok(())
+        }
+        Some(code) => Err(format!("Expected the bad token to be rejected with a 401 or 403, got {}.", code)),
+        None => Err(String::from("The request succeeded even though its token was bad.")),
+    }
+} // end rejected_token_validator
+
+/*
+ * This function checks that a response is the common Error shape carrying
+ * the expected status code, for tests that expect the server to refuse
+ * their request.
+ */
+pub fn error_validator(_request: &str, response: String, expected_code: u16) -> Result<(), String> {
+    let response = parse_response::<messages::Error>(&response)?;
+
+    if response.code != expected_code {
+        return Err(format!("Expected error code {}, got {}: {}", expected_code, response.code, response.message));
+    }
+
+    event!(Level::INFO, "The server responded with the expected {}: {}", response.code, response.message);
+
+    Ok(())
+} // end error_validator
+
+/*
+ * This function parses a response as the type its endpoint sends, or says
+ * where the response stops matching that type when it can't be.
+ */
+fn parse_response<T: DeserializeOwned>(response: &str) -> Result<T, String> {
+    serde_json::from_str::<T>(response).map_err(|e| {
+        let type_name = std::any::type_name::<T>().rsplit("::").next().unwrap_or_default();
+        let article = if type_name.starts_with(['A', 'E', 'I', 'O', 'U']) { "an" } else { "a" };
+
+        format!("Response is valid JSON but not {} {}: {}", article, type_name, e)
+    })
+} // end parse_response
+
+pub fn create_message_validator(_request: &str, response: String) -> Result<(), String> {
+    let response = parse_response::<SendNewMessageResponse>(&response)?;
+
+    match response.id {
+        Some(id) => event!(Level::INFO, "The message was created with id {}.", id),
+        None => event!(Level::INFO, "The message was accepted without an id (204 No Content)."),
+    }
+
+    Ok(())
+} // end create_message_validator
+
+pub fn get_messages_validator(_request: &str, response: String) -> Result<(), String> {
+    let response = parse_response::<GetMessagesResponse>(&response)?;
+
+    geo_tags_validator(&response.messages)
+} // end get_messages_validator
+
+pub fn get_users_validator(_request: &str, response: String) -> Result<(), String> {
+    let response = parse_response::<GetUsersResponse>(&response)?;
+
+    let empty_user_names = response
+        .user_names
+        .iter()
+        .filter(|user_name| user_name.trim().is_empty())
+        .count();
+
+    if empty_user_names > 0 {
+        return Err(format!("{} of the {} user names are empty.",
+            empty_user_names,
+            response.user_names.len()));
+    }
+
+    Ok(())
+} // end get_users_validator
+
+/*
+ * This function checks a Search Messages response, including whether its
+ * messages carry geo-tags as the request asked.
+ */
+pub fn search_messages_response_validator(
+    _request:           &str,
+    response:           String,
+    geo_tags_requested: bool,
+) -> Result<(), String> {
+    let response = parse_response::<SearchMessagesResponse>(&response)?;
+
+    let tagged = response.messages
+        .iter()
+        .filter(|message| message.geoTags.is_some())
+        .count();
+
+    if geo_tags_requested && !response.messages.is_empty() && tagged == 0 {
+        return Err(String::from("Geo-tags were requested but none of the messages include them."));
+    }
+
+    if !geo_tags_requested && tagged > 0 {
+        return Err(format!("Geo-tags were not requested but {} of the messages include them.", tagged));
+    }
+
+    geo_tags_validator(&response.messages)
+} // end search_messages_response_validator
+
+/*
+ * This function checks that each message a response carries is from the
+ * room named in the request, since a room-scoped request must never return
+ * messages from another room.  When strict, the response's own "roomName"
+ * field must match as well.  Every mismatch is reported.  Requests without
+ * a room pass.
+ */
+fn room_name_validator(request: &str, response: &str, strict: bool) -> Result<(), String> {
+    let request: serde_json::Value = serde_json::from_str(request).unwrap_or_default();
+    let response: serde_json::Value = serde_json::from_str(response).unwrap_or_default();
+
+    let room_name = match request["roomName"].as_str() {
+        Some(room_name) => room_name,
+        None => return Ok(()),
+    };
+
+    let mut problems: Vec<String> = Vec::new();
+
+    // Only strict checking holds the response itself to the room.
+    if strict {
+        if let Some(response_room_name) = response["roomName"].as_str() {
+            if response_room_name != room_name {
+                problems.push(format!("The response is for room {}, but the request was for room {}.",
+                    response_room_name,
+                    room_name));
+            }
+        }
+    }
+
+    for message in response["messages"].as_array().into_iter().flatten() {
+        if let Some(message_room_name) = message["roomName"].as_str() {
+            if message_room_name != room_name {
+                problems.push(format!("Message {} is from room {}, but the request was for room {}.",
+                    message["id"].as_str().unwrap_or("without an id"),
+                    message_room_name,
+                    room_name));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("; "))
+    }
+} // end room_name_validator
+
+/*
+ * This function compares two messages by the given sort orders, each
+ * breaking the ties of the one before it.  Timestamps are compared as
+ * instants when they parse, so that differing offsets or precision don't
+ * matter.  Relevance isn't carried by the messages, so it ties.
+ */
+fn compare_messages(sort: &SortFilter, first: &ChatMessageSchema, second: &ChatMessageSchema) -> Ordering {
+    let instant = |message: &ChatMessageSchema| OffsetDateTime::parse(&message.timestamp, &Iso8601::DEFAULT).ok();
+
+    sort.orders
+        .iter()
+        .map(|(direction, field)| {
+            let ordering = match field {
+                SortField::DOMAIN    => first.domainId.cmp(&second.domainId),
+                SortField::ROOM      => first.roomName.cmp(&second.roomName),
+                SortField::SENDER    => first.sender.cmp(&second.sender),
+                SortField::TIME      => match (instant(first), instant(second)) {
+                    (Some(first), Some(second)) => first.cmp(&second),
+                    _ => first.timestamp.cmp(&second.timestamp),
+                },
+                SortField::RELEVANCE => Ordering::Equal,
+            };
+
+            match direction {
+                SortDirection::ASC  => ordering,
+                SortDirection::DESC => ordering.reverse(),
+            }
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+} // end compare_messages
+
+/*
+ * This function checks that the messages gathered from every page of a
+ * search are in the order the search asked for, across the page
+ * boundaries as well as within each page.  The first pair out of order is
+ * reported.
+ */
+pub fn sort_order_validator(sort: &SortFilter, messages: &[ChatMessageSchema]) -> Result<(), String> {
+    match messages
+        .windows(2)
+        .position(|pair| compare_messages(sort, &pair[0], &pair[1]) == Ordering::Greater)
+    {
+        Some(index) => Err(format!("Message {} at position {} comes before message {}, out of the requested order.",
+            messages[index].id,
+            index,
+            messages[index + 1].id)),
+        None => Ok(()),
+    }
+} // end sort_order_validator
+
+/*
+ * This function checks that every message in a response contains at least
+ * one of the keywords the request searched for, ignoring case.  Every
+ * message that doesn't is reported.  Requests without keywords pass.
+ */
+fn keyword_match_validator(request: &str, response: &str) -> Result<(), String> {
+    let request: serde_json::Value = serde_json::from_str(request).unwrap_or_default();
+    let response: serde_json::Value = serde_json::from_str(response).unwrap_or_default();
+
+    let keywords: Vec<String> = request["keywords"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|keyword| keyword.as_str())
+        .map(|keyword| keyword.to_lowercase())
+        .collect();
+
+    if keywords.is_empty() {
+        return Ok(());
+    }
+
+    let mut problems: Vec<String> = Vec::new();
+
+    for message in response["messages"].as_array().into_iter().flatten() {
+        let text = message["text"].as_str().unwrap_or_default().to_lowercase();
+
+        if !keywords.iter().any(|keyword| text.contains(keyword.as_str())) {
+            problems.push(format!("Message {} does not contain any of the keywords {:?}.",
+                message["id"].as_str().unwrap_or("without an id"),
+                keywords));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("; "))
+    }
+} // end keyword_match_validator
+
+/*
+ * This function checks that a response, and every message it carries, is
+ * marked with the expected classification.  Only the Get Messages response
+ * and the messages in it and in a Search Messages response are marked, so
+ * the other endpoints have nothing to check.  It returns the path of each
+ * field that is missing or marked otherwise.
+ */
+fn classification_validator(expected: &str, server_path: &str, response: &str) -> Vec<String> {
+    let marks_response = match server_path {
+        "/messages" => true,
+        "/search"   => false,
+        _           => return Vec::new(),
+    };
+
+    let response: serde_json::Value = serde_json::from_str(response).unwrap_or_default();
+    let mut offending_fields: Vec<String> = Vec::new();
+
+    let mut check = |field: String, classification: &serde_json::Value| {
+        match classification.as_str() {
+            Some(classification) if classification == expected => {}
+            Some(classification) => {
+                error(format!("{} is {}, but {} was required.", field, classification, expected));
+                offending_fields.push(field);
+            }
+            None => {
+                error(format!("{} is missing, but {} was required.", field, expected));
+                offending_fields.push(field);
+            }
+        }
+    };
+
+    if marks_response {
+        check(String::from("classification"), &response["classification"]);
+    }
+
+    for (index, message) in response["messages"].as_array().into_iter().flatten().enumerate() {
+        check(format!("messages[{}].classification", index), &message["classification"]);
+    }
+
+    offending_fields
+} // end classification_validator
+
+/*
+ * This function checks that a search with no keywords was rejected with a
+ * 400 that points at the keywords field.
+ */
+pub fn empty_search_validator(_request: &str, response: String) -> Result<(), String> {
+    let response = parse_response::<ErrorCode400>(&response)?;
+
+    if response.code != 400 {
+        return Err(format!("Expected error code 400, got {}.", response.code));
+    }
+
+    if !response.fieldErrors.iter().any(|field_error| field_error.fieldName == "keywords") {
+        return Err(String::from("The 400 response has no field error for \"keywords\"."));
+    }
+
+    Ok(())
+} // end empty_search_validator
+
+/*
+ * This function walks a JSON value as the server sent it alongside the same
+ * value after a trip through the message structures, and warns about every
+ * floating point number that changed by more than PRECISION_LOSS_THRESHOLD.
+ */
+fn find_precision_loss(
+    sent:   &serde_json::Value,
+    stored: &serde_json::Value,
+    path:   &str,
+    losses: &mut usize,
+) {
+    match (sent, stored) {
+        (serde_json::Value::Number(sent), serde_json::Value::Number(stored)) if sent.is_f64() => {
+            let sent = sent.as_f64().unwrap_or_default();
+            let stored = stored.as_f64().unwrap_or_default();
+
+            if (sent - stored).abs() > PRECISION_LOSS_THRESHOLD {
+                event!(Level::WARN,
+                    "Precision loss at \"{}\": {} is stored as {} (off by {:e}).",
+                    path,
+                    sent,
+                    stored,
+                    (sent - stored).abs()
+                );
+                *losses += 1;
+            }
+        }
+        (serde_json::Value::Array(sent), serde_json::Value::Array(stored)) => {
+            for (index, (sent, stored)) in sent.iter().zip(stored).enumerate() {
+                find_precision_loss(sent, stored, &format!("{}/{}", path, index), losses);
+            }
+        }
+        (serde_json::Value::Object(sent), serde_json::Value::Object(stored)) => {
+            for (key, sent) in sent {
+                if let Some(stored) = stored.get(key) {
+                    find_precision_loss(sent, stored, &format!("{}/{}", path, key), losses);
+                }
+            }
+        }
+        _ => {}
+    }
+} // end find_precision_loss
+
+/*
+ * This function checks the geo-tags of every message in a response for
+ * numbers that lose precision when parsed into GeoTagSchema.  Geo-tags that
+ * don't parse are left to the other validators.  Returns how many such
+ * numbers were found.
+ */
+fn precision_loss_check(response: &str) -> usize {
+    let mut losses: usize = 0;
+
+    let value: serde_json::Value = match serde_json::from_str(response) {
+        Ok(value) => value,
+        Err(_) => return losses,
+    };
+
+    for (message_index, message) in value["messages"].as_array().into_iter().flatten().enumerate() {
+        for (tag_index, sent) in message["geoTags"].as_array().into_iter().flatten().enumerate() {
+            let stored = serde_json::from_value::<GeoTagSchema>(sent.clone())
+                .and_then(|geo_tag| serde_json::to_value(&geo_tag));
+
+            if let Ok(stored) = stored {
+                find_precision_loss(
+                    sent,
+                    &stored,
+                    &format!("/messages/{}/geoTags/{}", message_index, tag_index),
+                    &mut losses);
+            }
+        }
+    }
+
+    losses
+} // end precision_loss_check
+
+/*
+ * This function checks the number of matches in a search response against
+ * --assert_total_ge and --assert_total_eq.  The response's "total" field is
+ * used when the server sends one, otherwise the returned messages are
+ * counted.
+ */
+fn total_check(args: &Args, response: &str) -> Result<(), String> {
+    let response = serde_json::from_str::<SearchMessagesResponse>(response)
+        .map_err(|e| format!("Cannot check the total, the response is not a SearchMessagesResponse: {}", e))?;
+
+    let total = response.total.unwrap_or(response.messages.len());
+
+    if let Some(minimum) = args.assert_total_ge {
+        if total < minimum {
+            return Err(format!("Expected at least {} matches, got {}.", minimum, total));
+        }
+    }
+
+    if let Some(expected) = args.assert_total_eq {
+        if total != expected {
+            return Err(format!("Expected exactly {} matches, got {}.", expected, total));
+        }
+    }
+
+    Ok(())
+} // end total_check
+
+/*
+ * This function checks the number of user names in a Get Users response
+ * against --expect_min_users.
+ */
+fn user_count_check(args: &Args, response: &str) -> Result<(), String> {
+    let minimum = match args.expect_min_users {
+        Some(minimum) => minimum,
+        None => return Ok(()),
+    };
+
+    let response = serde_json::from_str::<GetUsersResponse>(response)
+        .map_err(|e| format!("Cannot count the users, the response is not a GetUsersResponse: {}", e))?;
+
+    if response.user_names.len() < minimum {
+        return Err(format!("Expected at least {} users, got {}.", minimum, response.user_names.len()));
+    }
+
+    Ok(())
+} // end user_count_check
+
+/*
+ * This function loads the JSON Schema files given with --response_schema,
+ * keyed by the endpoint path they apply to.
+ */
+fn load_response_schemas(args: &Args) -> Result<HashMap<String, jsonschema::Validator>, String> {
+    let mut schemas: HashMap<String, jsonschema::Validator> = HashMap::new();
+
+    for entry in &args.response_schema {
+        let (endpoint, path) = entry
+            .split_once('=')
+            .ok_or(format!("Expected <endpoint>=<path> for --response_schema, got {}", entry))?;
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read schema file {}: {}", path, e))?;
+
+        let schema: serde_json::Value = serde_json::from_str(&contents)
+            .map_err(|e| format!("Schema file {} is not valid JSON: {}", path, e))?;
+
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| format!("Schema file {} is not a valid JSON Schema: {}", path, e))?;
+
+        schemas.insert(String::from(endpoint), validator);
+    }
+
+    Ok(schemas)
+} // end load_response_schemas
+
+/*
+ * This function validates a response against a JSON Schema, logging every
+ * violation along with the location in the response where it occurred.
+ */
+fn schema_validator(schema: &jsonschema::Validator, response: &str) -> bool {
+    let instance: serde_json::Value = match serde_json::from_str(response) {
+        Ok(instance) => instance,
+        Err(e) => {
+            error(format!("Response is not valid JSON: {}", e));
+            return false;
+        }
+    };
+
+    let mut violations: usize = 0;
+
+    for violation in schema.iter_errors(&instance) {
+        error(format!("Schema violation at \"{}\": {}", violation.instance_path, violation));
+        violations += 1;
+    }
+
+    violations == 0
+} // end schema_validator
+
+// #############################################################################
+// #############################################################################
+//                                Test Runner
+// #############################################################################
+// #############################################################################
+
+//==============================================================================
+// struct TestContext
+//==============================================================================
+
+/// The TestContext structure holds what the tests of a run share: the
+/// arguments they run with, the response schemas, and the connection pool
+/// when --reuse_connection is given.
+#[derive(Clone)]
+pub struct TestContext {
+    pub args:           Arc<Args>,
+    pub schemas:        Arc<HashMap<String, jsonschema::Validator>>,
+    pub pool:           Option<Arc<ConnectionPool>>,
+}
+
+//==============================================================================
+// struct TestRun
+//==============================================================================
+
+/// The TestRun structure records the results of one pass over the test list
+/// and whether it was cut short by --abort_after_failures or --fail_fast.
+pub struct TestRun {
+    pub results:        Vec<TestResult>,
+    pub aborted:        bool,
+}
+
+//==============================================================================
+// struct TestResult
+//==============================================================================
+
+/// The TestResult structure records the outcome of running a single
+/// TestCase, including why it failed when it did.
+pub struct TestResult {
+    pub name:           String,
+    pub server_path:    String,
+    pub jwt_header_alg: Algorithm,
+    pub passed:         bool,
+    pub failure:        Option<String>,
+
+    // The size of the response in bytes, if one was received.
+    pub response_size:  Option<usize>,
+
+    // The response text, if it was well-formed JSON.
+    pub response:       Option<String>,
+
+    // The response as received, whether or not it could be parsed, for
+    // reporting why a test failed.
+    pub payload:        Option<String>,
+
+    // How long the test took, from connecting to receiving the response.
+    pub latency:        std::time::Duration,
+}
+
+/*
+ * This function sends the test's request to its endpoint and hands the
+ * response to the validator, provided the response is well-formed JSON.
+ */
+async fn run_test(context: &TestContext, test: &TestCase) -> TestResult {
+    let args = context.args.as_ref();
+    let name = test.name.as_str();
+    let server_path = test.server_path.as_str();
+    let jwt_header_alg = test.jwt_header_alg;
+    let request = &test.request;
+    let schema = context.schemas.get(server_path);
+    let validator = test.validator.as_ref();
+    let expect_rejection = matches!(test.kind, TestKind::Rejected { .. });
+    let pool = context.pool.as_deref();
+
+    event!(Level::INFO, "Beginning {} Test.", name);
+
+    let started = std::time::Instant::now();
+
+    // A refused token has to be tested on a connection of its own, and
+    // the injected faults drop or corrupt the connection they are used on.
+    let sent = match pool {
+        Some(pool) if !expect_rejection && args.fault.is_empty() => {
+            pool.send(args, SERVER_PORT, jwt_header_alg, server_path, request.clone()).await
+        }
+        _ => ws_connect_send(args, SERVER_PORT, jwt_header_alg, server_path, request.clone()).await,
+    };
+
+    let (response, connect_error) = match sent {
+        Ok(response) => (response, None),
+        Err(e) => (None, Some(e)),
+    };
+
+    let response_size = response.as_ref().map(|payload| payload.len());
+    let raw_payload = response
+        .as_ref()
+        .map(|payload| String::from_utf8_lossy(&payload.clone().into_data()).into_owned());
+    let mut response_text: Option<String> = None;
+
+    let outcome: Result<(), String> = match response {
+        // The server closed the connection instead of responding, which is
+        // one way to refuse a bad token.
+        Some(Message::Close(frame)) => {
+            let (code, reason) = close_code_and_reason(frame.as_ref());
+
+            if expect_rejection {
+                event!(Level::INFO, "The server closed the connection with {} ({:?}): {}", u16::from(code), code, reason);
+                Ok(())
+            } else {
+                Err(format!("The server closed the connection with {} ({:?}): {}", u16::from(code), code, reason))
+            }
+        }
+        Some(payload) => {
+            debug(format!("{}", payload));
+
+            match check_payload(&payload) {
+                PayloadCheck::NotUtf8 { valid_up_to } => {
+                    Err(format!("Response is not valid UTF-8 after byte {}.", valid_up_to))
+                }
+                PayloadCheck::NotJson { offset, error: e } => {
+                    Err(format!("Response is not valid JSON at byte {}: {}", offset, e))
+                }
+                PayloadCheck::TooDeep { offset } => {
+                    Err(format!("Response is malformed or adversarial: it nests deeper than {} levels at byte {}.",
+                        MAX_JSON_DEPTH,
+                        offset))
+                }
+                PayloadCheck::Json { text } => {
+                    response_text = Some(text.clone());
+
+                    if args.check_precision {
+                        let losses = precision_loss_check(&text);
+
+                        if losses > 0 {
+                            event!(Level::WARN,
+                                "{} geo-tag numbers in the response lose precision when parsed.",
+                                losses);
+                        }
+                    }
+
+                    let validated = if args.schema_only && schema.is_some() {
+                        Ok(())
+                    } else {
+                        validator(request, text.clone())
+                    };
+
+                    // Each check runs only if the ones before it passed,
+                    // so the failure reason is the first problem found.
+                    validated
+                        .and_then(|()| match schema {
+                            Some(schema) if !schema_validator(schema, &text) => {
+                                Err(String::from("Response does not match the JSON Schema."))
+                            }
+                            _ => Ok(()),
+                        })
+                        .and_then(|()| room_name_validator(request, &text, args.strict_room_name))
+                        .and_then(|()| if args.check_search_keywords {
+                            keyword_match_validator(request, &text)
+                        } else {
+                            Ok(())
+                        })
+                        .and_then(|()| {
+                            let misclassified_fields = match &args.require_classification {
+                                Some(expected) => classification_validator(expected, server_path, &text),
+                                None => Vec::new(),
+                            };
+
+                            if misclassified_fields.is_empty() {
+                                Ok(())
+                            } else {
+                                Err(format!("Response has the wrong classification in: {}",
+                                    misclassified_fields.join(", ")))
+                            }
+                        })
+                        .and_then(|()| match test.kind {
+                            TestKind::Search => total_check(args, &text),
+                            TestKind::UserList => user_count_check(args, &text),
+                            _ => Ok(()),
+                        })
+                }
+            }
+        }
+        // A server that refuses a bad token may do so by refusing the
+        // handshake or by closing the connection.  Failing to reach the
+        // server at all doesn't count.
+        None if expect_rejection => match connect_error {
+            Some(ConnectError::Handshake { status: Some(status), headers, .. })
+                if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN =>
+            {
+                event!(Level::INFO, "The server refused the handshake with {}.", status);
+
+                if let Some(challenge) = headers.get(WWW_AUTHENTICATE) {
+                    debug(format!("WWW-Authenticate: {}", String::from_utf8_lossy(challenge.as_bytes())));
+                }
+                Ok(())
+            }
+            Some(ConnectError::Handshake { status: Some(status), .. }) => {
+                Err(format!("The server refused the handshake with {} rather than 401 or 403.", status))
+            }
+            Some(ConnectError::Handshake { status: None, .. }) => {
+                event!(Level::INFO, "The server dropped the connection during the handshake.");
+                Ok(())
+            }
+            Some(e) => Err(format!("The rejection could not be verified: {}", e)),
+            None => {
+                event!(Level::INFO, "The server closed the connection without responding.");
+                Ok(())
+            }
+        },
+        None => match connect_error {
+            Some(e) => Err(e.to_string()),
+            None => Err(String::from("No response received.")),
+        },
+    };
+
+    // The latency covers connecting, the round trip, and validation.
+    let latency = started.elapsed();
+
+    match &outcome {
+        Ok(()) => {
+            event!(Level::INFO, "{} Test passed in {} ms!", name, latency.as_millis());
+        }
+        Err(reason) => {
+            error(reason.clone());
+            error(format!("{} Test Failed after {} ms!", name, latency.as_millis()));
+        }
+    }
+
+    TestResult {
+        name:           String::from(name),
+        server_path:    String::from(server_path),
+        jwt_header_alg,
+        passed:         outcome.is_ok(),
+        failure:        outcome.err(),
+        response_size,
+        response:       response_text,
+        payload:        raw_payload,
+        latency,
+    }
+} // end run_test
+
+/*
+ * This function sends the test's request to its endpoint and passes if the
+ * server then closes the connection with the expected code.
+ */
+async fn run_close_test(context: &TestContext, test: &TestCase, expected: CloseCode) -> TestResult {
+    let args = context.args.as_ref();
+    let name = test.name.as_str();
+    let server_path = test.server_path.as_str();
+    let jwt_header_alg = test.jwt_header_alg;
+    let request = test.request.clone();
+
+    event!(Level::INFO, "Beginning {} Test.", name);
+
+    let started = std::time::Instant::now();
+
+    let outcome = match ws_connect_send_until_close(args, SERVER_PORT, jwt_header_alg, server_path, request).await {
+        Ok(Some((code, reason))) if code == expected => {
+            event!(Level::INFO, "The server closed the connection with {} ({:?}): {}", u16::from(code), code, reason);
+            Ok(())
+        }
+        Ok(Some((code, reason))) => {
+            Err(format!("The server closed the connection with {} ({:?}) rather than {} ({:?}): {}",
+                u16::from(code),
+                code,
+                u16::from(expected),
+                expected,
+                reason))
+        }
+        Ok(None) => Err(format!("The server did not close the connection with {} ({:?}).",
+            u16::from(expected),
+            expected)),
+        Err(e) => Err(e.to_string()),
+    };
+
+    let latency = started.elapsed();
+
+    match &outcome {
+        Ok(()) => {
+            event!(Level::INFO, "{} Test passed in {} ms!", name, latency.as_millis());
+        }
+        Err(reason) => {
+            error(reason.clone());
+            error(format!("{} Test Failed after {} ms!", name, latency.as_millis()));
+        }
+    }
+
+    TestResult {
+        name:           String::from(name),
+        server_path:    String::from(server_path),
+        jwt_header_alg,
+        passed:         outcome.is_ok(),
+        failure:        outcome.err(),
+        response_size:  None,
+        response:       None,
+        payload:        None,
+        latency,
+    }
+} // end run_close_test
+
+/*
+ * This function fetches the room's messages and tells whether any of them
+ * is a match, or None if the messages could not be fetched at all.
+ */
+async fn room_has_message<F>(args: &Args, is_match: F) -> Option<bool>
+    where F: Fn(&ChatMessageSchema) -> bool
+{
+    let follow_up = ws_connect_send(
+        args,
+        SERVER_PORT,
+        Algorithm::HS256,
+        "/messages",
+        build_messages_request(&args.domain(), args.room())).await;
+
+    let messages = follow_up
+        .ok()
+        .flatten()
+        .and_then(|payload| payload.into_text().ok())
+        .and_then(|text| GetMessagesResponse::try_from_json(text).ok())?;
+
+    Some(messages.messages.iter().any(is_match))
+} // end room_has_message
+
+/*
+ * This function follows up a Send New Message request by fetching the
+ * room's messages and checking that one of them carries the text that was
+ * sent.  Unlike verify_sent_message, it doesn't rely on the server handing
+ * back the new message's id.
+ */
+async fn verify_read_back(args: &Args, request: &str) -> Result<(), String> {
+    let text = serde_json::from_str::<SendNewMessageRequest>(request)
+        .map_err(|e| format!("Could not read the text back out of the request: {}", e))?
+        .text;
+
+    match room_has_message(args, |message| message.text == text).await {
+        Some(true) => {
+            debug(format!("Read back the sent message \"{}\".", text));
+            Ok(())
+        }
+        Some(false) => Err(format!("The sent message \"{}\" is not among the room's messages.", text)),
+        None => Err(String::from("Could not fetch the room's messages to read back the sent message.")),
+    }
+} // end verify_read_back
+
+/*
+ * This function follows up a successful Send New Message response that
+ * carries an id by fetching the room's messages and checking that the new
+ * message is among them.  Responses without an id have nothing to verify.
+ */
+async fn verify_sent_message(args: &Args, response: &str) -> Result<(), String> {
+    let id = match serde_json::from_str::<SendNewMessageResponse>(response) {
+        Ok(SendNewMessageResponse { id: Some(id), .. }) => id,
+        _ => return Ok(()),
+    };
+
+    match room_has_message(args, |message| message.id == id).await {
+        Some(true) => {
+            debug(format!("Verified that message {} is in the room.", id));
+            Ok(())
+        }
+        Some(false) => Err(format!("Message {} was created but is not among the room's messages.", id)),
+        None => Err(format!("Could not fetch the room's messages to verify message {}.", id)),
+    }
+} // end verify_sent_message
+
+/*
+ * This function returns the value at the given percentile of a sorted
+ * list, using the nearest-rank method.
+ */
+pub fn percentile(sorted: &[usize], percent: usize) -> usize {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+
+    sorted[rank - 1]
+}
+
+/*
+ * This function logs the distribution of the response sizes seen during
+ * the run.
+ */
+fn log_response_sizes(response_sizes: &[usize]) {
+    if response_sizes.is_empty() {
+        event!(Level::INFO, "Response sizes: no responses received.");
+        return;
+    }
+
+    let mut sorted = response_sizes.to_vec();
+    sorted.sort_unstable();
+
+    event!(Level::INFO,
+        "Response sizes (bytes): min {}, p50 {}, p99 {}, max {} over {} responses",
+        sorted[0],
+        percentile(&sorted, 50),
+        percentile(&sorted, 99),
+        sorted[sorted.len() - 1],
+        sorted.len()
+    );
+} // end log_response_sizes
+
+/*
+ * This function logs the fastest, slowest, and average latency of the
+ * tests that were run.
+ */
+fn log_latencies(results: &[TestResult]) {
+    let latencies: Vec<u128> = results
+        .iter()
+        .map(|result| result.latency.as_millis())
+        .collect();
+
+    let (Some(min), Some(max)) = (latencies.iter().min(), latencies.iter().max()) else {
+        return;
+    };
+
+    event!(Level::INFO,
+        "Latency (ms): min {}, max {}, avg {} over {} tests",
+        min,
+        max,
+        latencies.iter().sum::<u128>() / latencies.len() as u128,
+        latencies.len()
+    );
+} // end log_latencies
+
+/*
+ * This function reports the results of a run: the summary in the chosen
+ * format, plus the JUnit XML and JSON reports if they were asked for.
+ */
+fn report_results(args: &Args, results: &[TestResult], total_tests: usize) {
+    log_latencies(results);
+    report_summary(args.summary_format, results, total_tests);
+
+    if let Some(path) = &args.report_junit {
+        match write_junit_report(path, results, total_tests) {
+            Ok(()) => event!(Level::INFO, "Wrote the JUnit report to {}.", path),
+            Err(e) => error(e),
+        }
+    }
+
+    if let Some(path) = &args.report_json {
+        match write_json_report(path, results, total_tests) {
+            Ok(()) => event!(Level::INFO, "Wrote the JSON report to {}.", path),
+            Err(e) => error(e),
+        }
+    }
+} // end report_results
+
+/*
+ * This function runs a single TestCase to completion, including the
+ * follow-up verification of a sent message when that was requested.
+ */
+async fn run_test_case(context: TestContext, test: TestCase) -> TestResult {
+    let args = match test.kind {
+        TestKind::Rejected { token: BadToken::Expired, .. } => {
+            Arc::new(Args { jwt_expired: true, ..(*context.args).clone() })
+        }
+        TestKind::Rejected { token: BadToken::WrongKey, .. } => Arc::new(Args {
+            jwt_secret: Some(String::from(WRONG_JWT_SECRET)),
+            ..(*context.args).clone()
+        }),
+        _ => context.args,
+    };
+    let context = TestContext { args: args.clone(), ..context };
+
+    if let TestKind::Rejected { close_code: Some(expected), .. } = test.kind {
+        return run_close_test(&context, &test, expected).await;
+    }
+
+    let mut result = run_test(&context, &test).await;
+
+    if result.passed && test.kind == TestKind::RoundTrip {
+        if let Err(reason) = verify_read_back(&args, &test.request).await {
+            error(reason.clone());
+            error(format!("{} Test Failed!", result.name));
+            result.passed = false;
+            result.failure = Some(reason);
+        }
+    }
+
+    if result.passed && args.verify_sent_message && test.server_path == "/send" {
+        if let Err(reason) = verify_sent_message(&args, result.response.as_deref().unwrap_or_default()).await {
+            error(reason.clone());
+            error(format!("{} Test Failed!", result.name));
+            result.passed = false;
+            result.failure = Some(reason);
+        }
+    }
+
+    result
+} // end run_test_case
+
+/*
+ * This function runs the tests in the list concurrently, at most
+ * --max_concurrency at a time, and reports how many of them passed.
+ * Results are reported in the order of the list no matter which test
+ * finishes first.  It returns the result of every test that ran.
+ */
+pub async fn run_test_list(args: &Args, test_list: Vec<TestCase>) -> TestRun {
+    let mut results: Vec<Option<TestResult>> = Vec::new();
+    let mut tests_failed: usize = 0;
+    let mut aborted = false;
+    let mut recent_failures: VecDeque<String> = VecDeque::new();
+    let mut response_sizes: Vec<usize> = Vec::new();
+    let mut field_coverage = FieldCoverage::new();
+    let total_tests: usize = test_list.len();
+
+    let schemas = match load_response_schemas(args) {
+        Ok(schemas) => Arc::new(schemas),
+        Err(e) => {
+            error(e);
+            return TestRun {
+                results:    Vec::new(),
+                aborted:    false,
+            };
+        }
+    };
+
+    // --fail_fast is the same as giving up after the first failure.
+    let abort_after_failures = if args.fail_fast {
+        Some(1)
+    } else {
+        args.abort_after_failures
+    };
+
+    let context = TestContext {
+        args:       Arc::new(args.clone()),
+        schemas,
+        pool:       args.reuse_connection.then(|| Arc::new(ConnectionPool::new())),
+    };
+    let max_concurrency = args.max_concurrency.max(1);
+    let mut pending = test_list.into_iter().enumerate();
+    let mut tasks: JoinSet<TestResult> = JoinSet::new();
+
+    // Each running task maps back to its place in the list, so that a test
+    // which panics can still be reported under its own name.
+    let mut running: HashMap<task::Id, (usize, String, String, Algorithm)> = HashMap::new();
+
+    loop {
+        // After Ctrl-C or an abort, let the running tests finish and close
+        // their connections, but don't start any more.
+        while tasks.len() < max_concurrency && !shutdown::is_requested() {
+            let Some((index, test)) = pending.next() else { break };
+            let name = test.name.clone();
+            let server_path = test.server_path.clone();
+            let jwt_header_alg = test.jwt_header_alg;
+
+            let handle = tasks.spawn(run_test_case(context.clone(), test));
+
+            running.insert(handle.id(), (index, name, server_path, jwt_header_alg));
+            results.push(None);
+        }
+
+        let (id, joined) = match tasks.join_next_with_id().await {
+            Some(Ok((id, result))) => (id, Ok(result)),
+            Some(Err(e)) => (e.id(), Err(e)),
+            None => break,
+        };
+
+        let (index, name, server_path, jwt_header_alg) = running.remove(&id).unwrap();
+
+        // A panic in one test is reported as that test's failure rather
+        // than taking the rest of the run down with it.
+        let result = match joined {
+            Ok(result) => result,
+            Err(e) => {
+                let reason = format!("Test task did not complete: {}", e);
+
+                error(reason.clone());
+                error(format!("{} Test Failed!", name));
+
+                TestResult {
+                    name,
+                    server_path:    server_path.clone(),
+                    jwt_header_alg,
+                    passed:         false,
+                    failure:        Some(reason),
+                    response_size:  None,
+                    response:       None,
+                    payload:        None,
+                    latency:        std::time::Duration::ZERO,
+                }
+            }
+        };
+
+        if let Some(response_size) = result.response_size {
+            response_sizes.push(response_size);
+        }
+
+        if let Some(response) = &result.response {
+            field_coverage.record(&server_path, response);
+        }
+
+        if result.passed {
+            results[index] = Some(result);
+            continue;
+        }
+
+        tests_failed += 1;
+
+        if recent_failures.len() == RECENT_FAILURES_REPORTED {
+            recent_failures.pop_front();
+        }
+        recent_failures.push_back(format!("{}: {}",
+            result.name,
+            result.failure.as_deref().unwrap_or_default()));
+
+        results[index] = Some(result);
+
+        // Stop hammering a server that is clearly broken.  The tests
+        // already running are left to finish and close their connections.
+        if let Some(abort_after_failures) = abort_after_failures {
+            if !aborted && tests_failed >= abort_after_failures {
+                error(format!("Aborting the run after {} failures.", tests_failed));
+
+                for failure in &recent_failures {
+                    error(format!("    {}", failure));
+                }
+
+                aborted = true;
+                shutdown::request();
+            }
+        }
+    }
+
+    if args.report_sizes {
+        log_response_sizes(&response_sizes);
+    }
+
+    if args.field_coverage {
+        field_coverage.log_report();
+    }
+
+    if let Some(pool) = &context.pool {
+        pool.close_all(args).await;
+    }
+
+    let results: Vec<TestResult> = results.into_iter().flatten().collect();
+    report_results(args, &results, total_tests);
+
+    TestRun {
+        results,
+        aborted,
+    }
+} // end run_test_list
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Get Messages response with one geo-tagged message, with coordinates
+    // given to more places than an f32 can hold.
+    const GEO_TAGGED_RESPONSE: &str = r#"{
+        "classification": "UNCLASSIFIED",
+        "messages": [{
+            "classification": "UNCLASSIFIED",
+            "domainId": "chatsurferxmppunclass",
+            "geoTags": [{
+                "anchorEnd": 7,
+                "anchorStart": 0,
+                "anchorText": "Norfolk",
+                "confidence": 0.987654321012,
+                "location": {
+                    "coordinates": {
+                        "point_coordinates": [-76.285873412345, 36.850768123456],
+                        "polygon_coordinates": [
+                            [-76.2901234567, 36.8401234567],
+                            [-76.2701234567, 36.8401234567],
+                            [-76.2701234567, 36.8601234567],
+                            [-76.2901234567, 36.8601234567]
+                        ]
+                    },
+                    "type": "Point"
+                },
+                "regions": [{
+                    "abbreviation": "VA",
+                    "bounds": [-83.675395123, 36.540738456, -75.242266789, 39.466012345],
+                    "description": "Commonwealth of Virginia",
+                    "name": "Virginia",
+                    "regionType": "State"
+                }],
+                "type": "LOCATION"
+            }],
+            "id": "6e4b6e86-030b-41ed-90ab-c05325526a01",
+            "roomName": "edge-view-test-room",
+            "sender": "test.user",
+            "text": "Norfolk is clear.",
+            "threadId": "6e4b6e86-030b-41ed-90ab-c05325526a02",
+            "timestamp": "2024-05-01T12:00:00.000Z",
+            "userId": "6e4b6e86-030b-41ed-90ab-c05325526a03"
+        }]
+    }"#;
+
+    #[test]
+    fn geo_tags_keep_their_precision_when_parsed() {
+        assert_eq!(precision_loss_check(GEO_TAGGED_RESPONSE), 0);
+    }
+
+    #[test]
+    fn precision_loss_is_counted_at_each_number() {
+        // Both coordinates move by more than PRECISION_LOSS_THRESHOLD when
+        // squeezed through an f32.
+        let (longitude, latitude): (f64, f64) = (-76.2851234567, 36.8401234567);
+        let sent = serde_json::json!({ "coordinates": [longitude, latitude], "count": 2 });
+        let stored = serde_json::json!({
+            "coordinates": [longitude as f32 as f64, latitude as f32 as f64],
+            "count": 2
+        });
+        let mut losses: usize = 0;
+
+        find_precision_loss(&sent, &stored, "", &mut losses);
+
+        assert_eq!(losses, 2);
+    }
+
+    // A response marked at the top level, with one message marked otherwise
+    // and one not marked at all.
+    const MIXED_CLASSIFICATION_RESPONSE: &str = r#"{
+        "classification": "SECRET",
+        "messages": [
+            { "classification": "SECRET", "text": "Convoy departed." },
+            { "classification": "UNCLASSIFIED", "text": "Convoy arrived." },
+            { "text": "Convoy refuelled." }
+        ]
+    }"#;
+
+    #[test]
+    fn classification_is_checked_on_get_messages_and_each_message() {
+        assert_eq!(classification_validator("SECRET", "/messages", MIXED_CLASSIFICATION_RESPONSE),
+            vec!(String::from("messages[1].classification"), String::from("messages[2].classification")));
+
+        assert_eq!(classification_validator("UNCLASSIFIED", "/messages", MIXED_CLASSIFICATION_RESPONSE),
+            vec!(
+                String::from("classification"),
+                String::from("messages[0].classification"),
+                String::from("messages[2].classification"),
+            ));
+    }
+
+    #[test]
+    fn search_response_is_checked_only_on_its_messages() {
+        let response = r#"{ "messages": [{ "classification": "SECRET" }], "total": 1 }"#;
+
+        assert!(classification_validator("SECRET", "/search", response).is_empty());
+        assert_eq!(classification_validator("UNCLASSIFIED", "/search", response),
+            vec!(String::from("messages[0].classification")));
+    }
+
+    #[test]
+    fn endpoints_without_markings_have_nothing_to_check() {
+        assert!(classification_validator("SECRET", "/users", r#"{ "userNames": ["test.user"] }"#).is_empty());
+        assert!(classification_validator("SECRET", "/send", r#"{ "id": "6e4b6e86-030b-41ed-90ab-c05325526a01" }"#).is_empty());
+    }
+
+    #[test]
+    fn room_name_is_checked_on_the_response_only_when_strict() {
+        let request = r#"{ "domainId": "chatsurferxmppunclass", "roomName": "edge-view-test-room" }"#;
+        let other_room = r#"{ "roomName": "other-room", "messages": [{ "roomName": "edge-view-test-room" }] }"#;
+        let other_message = r#"{ "messages": [{ "id": "6e4b6e86-030b-41ed-90ab-c05325526a01", "roomName": "other-room" }] }"#;
+
+        assert_eq!(room_name_validator(request, other_room, false), Ok(()));
+        assert!(room_name_validator(request, other_room, true).is_err());
+
+        for strict in [false, true] {
+            assert_eq!(room_name_validator(request, other_message, strict),
+                Err(String::from("Message 6e4b6e86-030b-41ed-90ab-c05325526a01 is from room other-room, but the request was for room edge-view-test-room.")));
+        }
+    }
+
+    #[test]
+    fn sort_order_is_checked_across_the_whole_list() {
+        let message = |id: &str, timestamp: &str| {
+            let mut message = ChatMessageSchema::test("Hello");
+            message.id = uuid::Uuid::parse_str(id).unwrap();
+            message.timestamp = String::from(timestamp);
+            message
+        };
+        let oldest_first = SortFilter { orders: vec![(SortDirection::ASC, SortField::TIME)] };
+        let newest_first = SortFilter { orders: vec![(SortDirection::DESC, SortField::TIME)] };
+
+        // The last message of one page and the first of the next.
+        let messages = vec![
+            message("6e4b6e86-030b-41ed-90ab-c05325526a01", "2024-05-01T12:00:00.000Z"),
+            message("6e4b6e86-030b-41ed-90ab-c05325526a02", "2024-05-01T14:30:00.000+02:00"),
+            message("6e4b6e86-030b-41ed-90ab-c05325526a03", "2024-05-01T12:15:00Z"),
+        ];
+
+        assert_eq!(sort_order_validator(&oldest_first, &messages),
+            Err(String::from("Message 6e4b6e86-030b-41ed-90ab-c05325526a02 at position 1 comes before message 6e4b6e86-030b-41ed-90ab-c05325526a03, out of the requested order.")));
+        assert_eq!(sort_order_validator(&newest_first, &messages),
+            Err(String::from("Message 6e4b6e86-030b-41ed-90ab-c05325526a01 at position 0 comes before message 6e4b6e86-030b-41ed-90ab-c05325526a02, out of the requested order.")));
+        assert_eq!(sort_order_validator(&oldest_first, &messages[..2]), Ok(()));
+        assert_eq!(sort_order_validator(&oldest_first, &[]), Ok(()));
+    }
+}